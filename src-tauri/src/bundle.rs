@@ -0,0 +1,79 @@
+mod cpp;
+mod prune;
+mod python;
+mod rust;
+mod source_map;
+
+use proc_macro2::LineColumn;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+pub use cpp::inline_includes;
+pub use prune::prune;
+pub use python::inline_imports;
+pub use rust::get_rust_modules;
+pub use source_map::SourceMap;
+
+/// How the library files used by a solution are found and put into the final code.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Bundler {
+    /// `lib_check_regex` finds the files, which `modifier` puts around the code
+    #[default]
+    Template,
+    /// Rust `use` and `mod` paths find the modules, which `modifier` puts around the code
+    Rust,
+    /// Quoted C and C++ `#include`s are replaced by the headers before `modifier` is applied
+    Cpp,
+    /// Local modules imported by Python code are put in front of it, loaded by an import hook
+    Python,
+}
+
+fn apply(source: &str, mut edits: Vec<(Range<usize>, String)>) -> String {
+    edits.sort_by_key(|(range, _)| std::cmp::Reverse(range.start));
+    let mut code = source.to_string();
+    for (range, text) in edits {
+        code.replace_range(range, &text);
+    }
+    code
+}
+
+/// Byte offsets in a source of the lines and columns proc-macro2 gives, which count chars.
+struct Offsets<'a> {
+    source: &'a str,
+    lines: Vec<usize>,
+}
+
+impl<'a> Offsets<'a> {
+    fn new(source: &'a str) -> Self {
+        let lines = std::iter::once(0)
+            .chain(source.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Offsets { source, lines }
+    }
+
+    fn get(&self, at: LineColumn) -> usize {
+        let start = self.lines[at.line - 1];
+        self.source[start..]
+            .char_indices()
+            .nth(at.column)
+            .map_or(self.source.len(), |(i, _)| start + i)
+    }
+
+    fn range(&self, range: Range<LineColumn>) -> Range<usize> {
+        self.get(range.start)..self.get(range.end)
+    }
+}
+
+/// Writes the files into a new temporary directory, which is returned.
+#[cfg(test)]
+fn write_files(files: &[(&str, &str)]) -> std::path::PathBuf {
+    let dir = std::env::temp_dir().join(uuid::Uuid::new_v4().to_string());
+    for (path, code) in files {
+        let path = dir.join(path);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, code).unwrap();
+    }
+    dir
+}
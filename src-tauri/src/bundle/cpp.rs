@@ -0,0 +1,281 @@
+use super::{prune::blank_comments, SourceMap};
+use crate::utils::{extract_code_block, extract_code_block_with_line, resolve_path};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Preprocessor directives the inliner looks at.
+struct Directives {
+    include: Regex,
+    pragma_once: Regex,
+    conditional: Regex,
+    define: Regex,
+}
+
+impl Directives {
+    fn new() -> Self {
+        Directives {
+            include: Regex::new(r#"^\s*#\s*include\s*"([^"]+)""#).unwrap(),
+            pragma_once: Regex::new(r"^\s*#\s*pragma\s+once\b").unwrap(),
+            conditional: Regex::new(r"^\s*#\s*(if|ifdef|ifndef|endif)\b\s*(\w*)").unwrap(),
+            define: Regex::new(r"^\s*#\s*define\s+(\w+)").unwrap(),
+        }
+    }
+
+    /// Macro of the guard around the whole header, as in `#ifndef X` `#define X` ... `#endif`.
+    fn include_guard(&self, lines: &[String]) -> Option<String> {
+        let mut lines = lines.iter().filter(|x| !x.trim().is_empty());
+        let guard = self
+            .conditional
+            .captures(lines.next()?)
+            .filter(|x| &x[1] == "ifndef")?[2]
+            .to_string();
+        if self.define.captures(lines.next()?)?[1] != guard {
+            return None;
+        }
+
+        // The `#endif` closing the `#ifndef` must be the last line
+        let mut depth = 1;
+        for line in lines {
+            if depth == 0 {
+                return None;
+            }
+            match self.conditional.captures(line).as_ref().map(|x| &x[1]) {
+                Some("endif") => depth -= 1,
+                Some(_) => depth += 1,
+                None => {}
+            }
+        }
+        (depth == 0).then_some(guard)
+    }
+}
+
+/// The code with its quoted includes replaced by the headers, recursively. Headers are looked
+/// up next to the file including them, then in the directories of `include`, and then among
+/// its files by their trailing path. System includes and headers that aren't found are kept.
+/// The code is returned with where its lines come from.
+pub fn inline_includes(
+    path: &Path,
+    source: &str,
+    include: &HashMap<String, String>,
+    dir: &Path,
+    line_directives: bool,
+) -> Result<(String, SourceMap), String> {
+    let roots = include
+        .iter()
+        .collect::<BTreeMap<_, _>>()
+        .into_values()
+        .map(|x| resolve_path(dir, x))
+        .collect();
+    let mut inliner = Inliner {
+        directives: Directives::new(),
+        roots,
+        line_directives,
+        once: HashSet::new(),
+        guards: HashSet::new(),
+        stack: vec![],
+    };
+    let mut code = String::new();
+    let mut map = SourceMap::default();
+    inliner.inline(path, source, &mut code, &mut map)?;
+    Ok((code, map))
+}
+
+struct Inliner {
+    directives: Directives,
+    roots: Vec<PathBuf>,
+    line_directives: bool,
+    /// Headers with `#pragma once` that were inlined
+    once: HashSet<PathBuf>,
+    /// Macros of the include guards of the headers that were inlined
+    guards: HashSet<String>,
+    /// Headers being inlined, to catch one that includes itself without a guard
+    stack: Vec<PathBuf>,
+}
+
+impl Inliner {
+    fn inline(
+        &mut self,
+        file: &Path,
+        source: &str,
+        code: &mut String,
+        map: &mut SourceMap,
+    ) -> Result<(), String> {
+        let (source, start) = extract_code_block_with_line(source);
+        if self.line_directives && !self.stack.is_empty() {
+            map.push(lines(code), None);
+            let _ = writeln!(code, "#line {} \"{}\"", start + 1, escape(file));
+        }
+        map.push(lines(code), Some((file, start)));
+
+        let stripped = strip_comments(&source);
+        for (i, (line, stripped)) in source.lines().zip(&stripped).enumerate() {
+            // Lines left out are kept empty, so the lines after them keep their numbers.
+            // `#pragma once` is only meaningful in a header, and a warning anywhere else.
+            if !self.stack.is_empty() && self.directives.pragma_once.is_match(stripped) {
+                code.push('\n');
+                continue;
+            }
+            let Some(header) = self
+                .directives
+                .include
+                .captures(stripped)
+                .and_then(|x| self.resolve(file, &x[1]))
+            else {
+                code.push_str(line);
+                code.push('\n');
+                continue;
+            };
+
+            let key = header.canonicalize().unwrap_or_else(|_| header.clone());
+            if self.once.contains(&key) {
+                code.push('\n');
+                continue;
+            }
+            if self.stack.contains(&key) {
+                return Err(format!(
+                    "{} includes itself without #pragma once or an include guard",
+                    header.display()
+                ));
+            }
+            let content = fs::read_to_string(&header)
+                .map_err(|e| format!("Failed to read file {:?}: {}", header, e))?;
+            let stripped_header = strip_comments(&extract_code_block(&content));
+            if let Some(guard) = self.directives.include_guard(&stripped_header) {
+                if !self.guards.insert(guard) {
+                    code.push('\n');
+                    continue;
+                }
+            }
+            if stripped_header
+                .iter()
+                .any(|x| self.directives.pragma_once.is_match(x))
+            {
+                self.once.insert(key.clone());
+            }
+
+            self.stack.push(key);
+            self.inline(&header, &content, code, map)?;
+            self.stack.pop();
+            if self.line_directives {
+                map.push(lines(code), None);
+                let _ = writeln!(code, "#line {} \"{}\"", start + i + 2, escape(file));
+            }
+            map.push(lines(code), Some((file, start + i + 1)));
+        }
+        Ok(())
+    }
+
+    fn resolve(&self, file: &Path, name: &str) -> Option<PathBuf> {
+        let local = file.parent().map(|x| x.join(name));
+        local
+            .into_iter()
+            .chain(self.roots.iter().filter_map(|root| {
+                if root.is_dir() {
+                    Some(root.join(name))
+                } else {
+                    root.ends_with(name).then(|| root.clone())
+                }
+            }))
+            .find(|x| x.is_file())
+    }
+}
+
+/// Lines of the source without comments, so commented out directives aren't followed.
+fn strip_comments(source: &str) -> Vec<String> {
+    blank_comments(source, false)
+        .lines()
+        .map(String::from)
+        .collect()
+}
+
+/// Number of the line being written at the end of the code.
+fn lines(code: &str) -> usize {
+    code.matches('\n').count()
+}
+
+fn escape(path: &Path) -> String {
+    path.to_string_lossy()
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::write_files;
+
+    fn guard(header: &str) -> Option<String> {
+        Directives::new().include_guard(&strip_comments(header))
+    }
+
+    fn inline(files: &[(&str, &str)], source: &str, line_directives: bool) -> (String, PathBuf) {
+        let dir = write_files(files);
+        let path = dir.join("main.cpp");
+        let code = inline_includes(&path, source, &HashMap::new(), &dir, line_directives);
+        let _ = fs::remove_dir_all(&dir);
+        (code.unwrap().0, dir)
+    }
+
+    #[test]
+    fn include_guards() {
+        assert_eq!(
+            guard("// dsu\n#ifndef DSU_H\n#define DSU_H\n#if X\n#endif\n#endif\n"),
+            Some("DSU_H".into())
+        );
+        assert_eq!(guard("#ifndef DSU_H\n#define OTHER\n#endif\n"), None);
+        assert_eq!(
+            guard("#ifndef DSU_H\n#define DSU_H\n#endif\nint x;\n"),
+            None
+        );
+        assert_eq!(guard("#ifdef DSU_H\n#define DSU_H\n#endif\n"), None);
+    }
+
+    #[test]
+    fn headers_are_inlined_once() {
+        let files = [
+            ("once.h", "#pragma once\nint once;\n"),
+            ("guarded.h", "#ifndef G\n#define G\nint guarded;\n#endif\n"),
+        ];
+        let source = "#include \"once.h\"\n#include \"guarded.h\"\n#include \"once.h\"\n#include \"guarded.h\"\n#include <vector>\n";
+        let (code, _) = inline(&files, source, false);
+        assert_eq!(code.matches("int once;").count(), 1);
+        assert_eq!(code.matches("int guarded;").count(), 1);
+        assert!(!code.contains("#pragma once"));
+        assert!(code.contains("#include <vector>"));
+    }
+
+    #[test]
+    fn includes_in_comments_are_kept() {
+        let files = [("a.h", "int a;\n")];
+        let source = "// #include \"a.h\"\n/*\n#include \"a.h\"\n*/\nint main() {}\n";
+        let (code, _) = inline(&files, source, false);
+        assert_eq!(code, source);
+    }
+
+    #[test]
+    fn line_directives_number_the_lines() {
+        let files = [("a.h", "int a;\nint b;\n")];
+        let source = "#include <cstdio>\n#include \"a.h\"\nint main() {}\n";
+        let (code, dir) = inline(&files, source, true);
+        let expected = format!(
+            "#include <cstdio>\n#line 1 \"{}\"\nint a;\nint b;\n#line 3 \"{}\"\nint main() {{}}\n",
+            escape(&dir.join("a.h")),
+            escape(&dir.join("main.cpp"))
+        );
+        assert_eq!(code, expected);
+    }
+
+    #[test]
+    fn headers_including_themselves() {
+        let dir = write_files(&[("a.h", "#include \"a.h\"\n")]);
+        let path = dir.join("main.cpp");
+        let result = inline_includes(&path, "#include \"a.h\"\n", &HashMap::new(), &dir, false);
+        let _ = fs::remove_dir_all(&dir);
+        assert!(result.is_err());
+    }
+}
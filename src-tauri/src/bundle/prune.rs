@@ -0,0 +1,927 @@
+use super::{apply, Offsets};
+use proc_macro2::{extra::invalidate_current_thread_spans, LineColumn, TokenStream, TokenTree};
+use regex::Regex;
+use std::{collections::HashSet, ops::Range, path::Path};
+use syn::{
+    spanned::Spanned,
+    token::Comma,
+    visit::{self, Visit},
+    Item, ItemMacro, ItemMod, TraitItem, UseTree,
+};
+
+/// The code without the top-level functions, types and impls that `main` doesn't use,
+/// directly or through other items, for Rust and C or C++ code. Uses are told by names, so
+/// an item is kept when anything it could be mistaken for is used. The ranges of the code
+/// that were removed are returned with it. None if the language isn't supported, the code
+/// has no `main` or there is nothing to remove.
+pub fn prune(code: &str, source_file: &str) -> Option<(String, Vec<Range<usize>>)> {
+    let extension = Path::new(source_file).extension()?.to_str()?;
+    match extension {
+        "rs" => {
+            let pruned = prune_rust(code);
+            // The spans of the parsed code are kept until then
+            invalidate_current_thread_spans();
+            pruned
+        }
+        "c" | "cc" | "cpp" | "cxx" => prune_cpp(code),
+        _ => None,
+    }
+}
+
+/// How a top-level item is kept.
+enum Kind {
+    /// Always, like `main` and items the pruner doesn't know about
+    Root,
+    /// When one of its names is used
+    Named(Vec<String>),
+    /// When the items of the names it is attached to are kept, like impls of types
+    Attached(Vec<String>),
+}
+
+struct Node {
+    kind: Kind,
+    /// Names used by the item
+    uses: HashSet<String>,
+    /// Code of the item, or None if it isn't removed on its own
+    range: Option<Range<usize>>,
+}
+
+/// Names of the kept items, and which nodes are kept.
+fn mark(nodes: &[Node]) -> (HashSet<String>, Vec<bool>) {
+    let defined: HashSet<_> = nodes
+        .iter()
+        .flat_map(|x| match &x.kind {
+            Kind::Named(names) => names.as_slice(),
+            _ => &[],
+        })
+        .collect();
+    let mut used = HashSet::new();
+    let mut kept_names = HashSet::new();
+    let mut kept = vec![false; nodes.len()];
+
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for (node, kept) in nodes.iter().zip(kept.iter_mut()) {
+            if *kept {
+                continue;
+            }
+            *kept = match &node.kind {
+                Kind::Root => true,
+                Kind::Named(names) => names.iter().any(|x| used.contains(x)),
+                Kind::Attached(owners) => owners
+                    .iter()
+                    .all(|x| !defined.contains(x) || kept_names.contains(x)),
+            };
+            if *kept {
+                if let Kind::Named(names) = &node.kind {
+                    kept_names.extend(names.iter().cloned());
+                }
+                used.extend(node.uses.iter().cloned());
+                changed = true;
+            }
+        }
+    }
+    (kept_names, kept)
+}
+
+/// Extends a range to the whole lines it is on, when nothing else is on them.
+fn whole_lines(code: &str, range: Range<usize>) -> Range<usize> {
+    let before = &code[..range.start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    if !before[line_start..].trim().is_empty() {
+        return range;
+    }
+    let after = &code[range.end..];
+    let line_end = after.find('\n').map_or(code.len(), |i| range.end + i + 1);
+    if !code[range.end..line_end].trim().is_empty() {
+        return range;
+    }
+    line_start..line_end
+}
+
+fn remove(code: &str, ranges: Vec<Range<usize>>) -> Option<(String, Vec<Range<usize>>)> {
+    if ranges.is_empty() {
+        return None;
+    }
+    let ranges: Vec<_> = ranges.into_iter().map(|x| whole_lines(code, x)).collect();
+    let edits = ranges.iter().map(|x| (x.clone(), String::new())).collect();
+    Some((apply(code, edits), ranges))
+}
+
+fn prune_rust(code: &str) -> Option<(String, Vec<Range<usize>>)> {
+    let file = syn::parse_file(code).ok()?;
+    let offsets = Offsets::new(code);
+    let mut items = RustItems {
+        offsets: &offsets,
+        nodes: vec![],
+        uses: vec![],
+        mods: HashSet::new(),
+        has_main: false,
+    };
+    items.add(&file.items, true);
+    if !items.has_main {
+        return None;
+    }
+
+    let (kept_names, kept) = mark(&items.nodes);
+    let pruned: HashSet<_> = items
+        .nodes
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| !**kept)
+        .flat_map(|(node, _)| match &node.kind {
+            Kind::Named(names) => names.as_slice(),
+            _ => &[],
+        })
+        .filter(|x| !kept_names.contains(*x))
+        .collect();
+
+    let mut ranges: Vec<_> = items
+        .nodes
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| !**kept)
+        .filter_map(|(node, _)| node.range.clone())
+        .collect();
+    // `use`s of removed items would no longer resolve
+    for item in &items.uses {
+        let local = match &item.tree {
+            UseTree::Path(path) => {
+                ["crate", "self", "super"].iter().any(|x| path.ident == x)
+                    || items.mods.contains(&path.ident.to_string())
+            }
+            _ => false,
+        };
+        if !local {
+            continue;
+        }
+        let mut leaves = vec![];
+        use_leaves(&item.tree, None, &offsets, &mut leaves);
+        let removed: Vec<_> = leaves
+            .iter()
+            .filter(|(name, _)| pruned.contains(name))
+            .collect();
+        if removed.len() == leaves.len() {
+            let span = item.span();
+            ranges.push(offsets.range(span.start()..span.end()));
+        } else {
+            ranges.extend(removed.into_iter().map(|(_, range)| range.clone()));
+        }
+    }
+    remove(code, ranges)
+}
+
+/// Names of the items a `use` brings in, with the code of each to remove it from its group,
+/// including its path in the group and the comma after it.
+fn use_leaves(
+    tree: &UseTree,
+    in_group: Option<(LineColumn, Option<&Comma>)>,
+    offsets: &Offsets,
+    leaves: &mut Vec<(String, Range<usize>)>,
+) {
+    let name = match tree {
+        UseTree::Path(path) => return use_leaves(&path.tree, in_group, offsets, leaves),
+        UseTree::Group(group) => {
+            for pair in group.items.pairs() {
+                let start = pair.value().span().start();
+                use_leaves(
+                    pair.value(),
+                    Some((start, pair.punct().copied())),
+                    offsets,
+                    leaves,
+                );
+            }
+            return;
+        }
+        UseTree::Name(name) if name.ident == "self" => return,
+        UseTree::Name(name) => &name.ident,
+        UseTree::Rename(rename) => &rename.ident,
+        UseTree::Glob(_) => return,
+    };
+    let (start, comma) = in_group.unwrap_or((tree.span().start(), None));
+    let end = comma.map_or(tree.span().end(), |x| x.span.end());
+    leaves.push((name.to_string(), offsets.range(start..end)));
+}
+
+/// Collects the top-level items of Rust code, and those of the modules declared in it.
+struct RustItems<'a> {
+    offsets: &'a Offsets<'a>,
+    nodes: Vec<Node>,
+    uses: Vec<syn::ItemUse>,
+    /// Names of the inline modules
+    mods: HashSet<String>,
+    has_main: bool,
+}
+
+impl RustItems<'_> {
+    fn add(&mut self, items: &[Item], crate_root: bool) {
+        for item in items {
+            let kind = match item {
+                Item::Fn(x) if crate_root && x.sig.ident == "main" => {
+                    self.has_main = true;
+                    Kind::Root
+                }
+                Item::Fn(x) => Kind::Named(vec![x.sig.ident.to_string()]),
+                Item::Struct(x) => Kind::Named(vec![x.ident.to_string()]),
+                Item::Enum(x) => Kind::Named(vec![x.ident.to_string()]),
+                Item::Union(x) => Kind::Named(vec![x.ident.to_string()]),
+                Item::Type(x) => Kind::Named(vec![x.ident.to_string()]),
+                Item::Const(x) if x.ident != "_" => Kind::Named(vec![x.ident.to_string()]),
+                Item::Static(x) => Kind::Named(vec![x.ident.to_string()]),
+                Item::Macro(ItemMacro {
+                    ident: Some(ident), ..
+                }) => Kind::Named(vec![ident.to_string()]),
+                // Methods are called without naming the trait
+                Item::Trait(x) => Kind::Named(
+                    x.items
+                        .iter()
+                        .filter_map(|item| match item {
+                            TraitItem::Fn(x) => Some(&x.sig.ident),
+                            TraitItem::Const(x) => Some(&x.ident),
+                            TraitItem::Type(x) => Some(&x.ident),
+                            _ => None,
+                        })
+                        .chain([&x.ident])
+                        .map(ToString::to_string)
+                        .collect(),
+                ),
+                Item::Impl(x) => {
+                    let mut owners = Idents::default();
+                    owners.visit_type(&x.self_ty);
+                    if let Some((_, path, _)) = &x.trait_ {
+                        owners.visit_path(path);
+                    }
+                    Kind::Attached(owners.0.into_iter().collect())
+                }
+                Item::Mod(ItemMod {
+                    ident,
+                    content: Some((_, items)),
+                    ..
+                }) => {
+                    self.mods.insert(ident.to_string());
+                    self.add(items, false);
+                    continue;
+                }
+                // The items of a glob are used without their path
+                Item::Use(x) if !has_glob(&x.tree) => {
+                    self.uses.push(x.clone());
+                    self.add_renames(&x.tree);
+                    continue;
+                }
+                _ => Kind::Root,
+            };
+            let mut uses = Idents::default();
+            uses.visit_item(item);
+            let span = item.span();
+            self.nodes.push(Node {
+                range: match kind {
+                    Kind::Root => None,
+                    _ => Some(self.offsets.range(span.start()..span.end())),
+                },
+                kind,
+                uses: uses.0,
+            });
+        }
+    }
+
+    /// An item renamed by a `use` is used through its new name.
+    fn add_renames(&mut self, tree: &UseTree) {
+        match tree {
+            UseTree::Path(path) => self.add_renames(&path.tree),
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.add_renames(tree);
+                }
+            }
+            UseTree::Rename(rename) => self.nodes.push(Node {
+                kind: Kind::Named(vec![rename.rename.to_string()]),
+                uses: HashSet::from([rename.ident.to_string()]),
+                range: None,
+            }),
+            _ => {}
+        }
+    }
+}
+
+fn has_glob(tree: &UseTree) -> bool {
+    match tree {
+        UseTree::Path(path) => has_glob(&path.tree),
+        UseTree::Group(group) => group.items.iter().any(has_glob),
+        UseTree::Glob(_) => true,
+        _ => false,
+    }
+}
+
+/// Every identifier in a syntax tree, including the ones in macros.
+#[derive(Default)]
+struct Idents(HashSet<String>);
+
+impl Idents {
+    fn tokens(&mut self, tokens: TokenStream) {
+        for token in tokens {
+            match token {
+                TokenTree::Group(group) => self.tokens(group.stream()),
+                TokenTree::Ident(ident) => {
+                    self.0.insert(ident.to_string());
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+impl<'ast> Visit<'ast> for Idents {
+    fn visit_ident(&mut self, ident: &'ast proc_macro2::Ident) {
+        self.0.insert(ident.to_string());
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+}
+
+fn prune_cpp(code: &str) -> Option<(String, Vec<Range<usize>>)> {
+    let clean = blank_comments(code, true);
+    let scanner = CppScanner::new(&clean);
+    let nodes = scanner.nodes()?;
+
+    let (_, kept) = mark(&nodes);
+    let ranges = nodes
+        .iter()
+        .zip(&kept)
+        .filter(|(_, kept)| !**kept)
+        .filter_map(|(node, _)| node.range.clone())
+        .collect();
+    remove(code, ranges)
+}
+
+/// The C or C++ code with comments, and with `literals` the contents of string and char
+/// literals, replaced by spaces, keeping every byte where it was, so it can be scanned for
+/// braces, names and directives.
+pub(super) fn blank_comments(code: &str, literals: bool) -> String {
+    let bytes = code.as_bytes();
+    let mut clean = bytes.to_vec();
+    let blank = |clean: &mut Vec<u8>, range: Range<usize>| {
+        for i in range {
+            if clean[i] != b'\n' {
+                clean[i] = b' ';
+            }
+        }
+    };
+    let blank_literal = |clean: &mut Vec<u8>, range: Range<usize>| {
+        if literals {
+            blank(clean, range);
+        }
+    };
+
+    let mut i = 0;
+    while i < bytes.len() {
+        let rest = &bytes[i..];
+        if rest.starts_with(b"//") {
+            let end = rest.iter().position(|&x| x == b'\n').unwrap_or(rest.len());
+            blank(&mut clean, i..i + end);
+            i += end;
+        } else if rest.starts_with(b"/*") {
+            let end = rest[2..]
+                .windows(2)
+                .position(|x| x == b"*/")
+                .map_or(rest.len(), |x| x + 4);
+            blank(&mut clean, i..i + end);
+            i += end;
+        } else if rest.starts_with(b"R\"")
+            && (i == 0 || !(bytes[i - 1].is_ascii_alphanumeric() || bytes[i - 1] == b'_'))
+        {
+            // Raw string, as in `R"delim(...)delim"`
+            let Some(open) = rest.iter().position(|&x| x == b'(') else {
+                i += 2;
+                continue;
+            };
+            let mut close = vec![b')'];
+            close.extend_from_slice(&rest[2..open]);
+            close.push(b'"');
+            let end = rest[open..]
+                .windows(close.len())
+                .position(|x| x == close.as_slice())
+                .map_or(rest.len(), |x| open + x + close.len());
+            blank_literal(&mut clean, i + 2..i + end - 1);
+            i += end;
+        } else if rest[0] == b'"' || (rest[0] == b'\'' && !(i > 0 && bytes[i - 1].is_ascii_digit()))
+        {
+            // A quote after a digit separates digits, as in `1'000'000`
+            let quote = rest[0];
+            let mut end = 1;
+            while end < rest.len() && rest[end] != quote && rest[end] != b'\n' {
+                end += if rest[end] == b'\\' { 2 } else { 1 };
+            }
+            let end = (end + 1).min(rest.len());
+            blank_literal(&mut clean, i + 1..i + end - 1);
+            i += end;
+        } else {
+            i += 1;
+        }
+    }
+    // Comments and literals end at ASCII bytes, so whole chars were blanked. Should that
+    // not hold, the lines must still be there for the callers that zip them with the code
+    String::from_utf8_lossy(&clean).into_owned()
+}
+
+/// Splits C and C++ code into its top-level declarations. Namespaces and `extern "C"` blocks
+/// are looked into, and preprocessor lines are kept.
+struct CppScanner<'a> {
+    clean: &'a str,
+    ident: Regex,
+    /// Name before the parameters of a function
+    function_name: Regex,
+    type_name: Regex,
+    transparent: Regex,
+}
+
+const CPP_KEYWORDS: [&str; 7] = [
+    "struct",
+    "class",
+    "union",
+    "enum",
+    "typedef",
+    "using",
+    "namespace",
+];
+
+impl<'a> CppScanner<'a> {
+    fn new(clean: &'a str) -> Self {
+        CppScanner {
+            clean,
+            ident: Regex::new(r"\b[A-Za-z_]\w*\b").unwrap(),
+            function_name: Regex::new(r"(::\s*)?~?\b([A-Za-z_]\w*)\s*(<[^()]*>)?\s*$").unwrap(),
+            type_name: Regex::new(
+                r"^(?:struct|class|union|enum(?:\s+(?:class|struct))?)\s+(?:alignas\s*\([^)]*\)\s*|\[\[[^\]]*\]\]\s*)*([A-Za-z_]\w*)\s*(<)?",
+            )
+            .unwrap(),
+            transparent: Regex::new(r#"^(?:(?:inline\s+)?namespace\b[\w\s:]*|extern\s*"[^"]*")$"#)
+                .unwrap(),
+        }
+    }
+
+    fn idents(&self, text: &str) -> HashSet<String> {
+        self.ident
+            .find_iter(text)
+            .map(|x| x.as_str().to_string())
+            .collect()
+    }
+
+    /// The declarations, or None if the code has no `main`.
+    fn nodes(&self) -> Option<Vec<Node>> {
+        let bytes = self.clean.as_bytes();
+        let mut nodes = vec![];
+        let mut has_main = false;
+        let mut open_blocks = 0;
+        let mut i = 0;
+        while i < bytes.len() {
+            match bytes[i] {
+                x if x.is_ascii_whitespace() || x == b';' => i += 1,
+                b'#' => {
+                    let end = self.line_end(i);
+                    nodes.push(Node {
+                        kind: Kind::Root,
+                        uses: self.idents(&self.clean[i..end]),
+                        range: None,
+                    });
+                    i = end;
+                }
+                b'}' if open_blocks > 0 => {
+                    open_blocks -= 1;
+                    i += 1;
+                }
+                _ => {
+                    let Some((end, body)) = self.declaration_end(i) else {
+                        // The head of a namespace, whose declarations are scanned next
+                        open_blocks += 1;
+                        i = self.clean[i..].find('{').map_or(bytes.len(), |x| i + x + 1);
+                        continue;
+                    };
+                    let kind = self.kind(i, end, body);
+                    if let Kind::Root = kind {
+                        has_main |= self.is_main(i, body);
+                    }
+                    nodes.push(Node {
+                        kind,
+                        uses: self.idents(&self.clean[i..end]),
+                        range: Some(i..end),
+                    });
+                    i = end;
+                }
+            }
+        }
+        has_main.then_some(nodes)
+    }
+
+    /// End of the preprocessor line starting at `start`, with the lines it continues on.
+    fn line_end(&self, start: usize) -> usize {
+        let bytes = self.clean.as_bytes();
+        let mut i = start;
+        while i < bytes.len() && bytes[i] != b'\n' {
+            if bytes[i] == b'\\' && bytes.get(i + 1) == Some(&b'\n') {
+                i += 1;
+            }
+            i += 1;
+        }
+        i
+    }
+
+    fn at_line_start(&self, i: usize) -> bool {
+        self.clean[..i]
+            .bytes()
+            .rev()
+            .take_while(|&x| x != b'\n')
+            .all(|x| x == b' ' || x == b'\t')
+    }
+
+    fn matching_brace(&self, open: usize) -> usize {
+        let mut depth = 0;
+        for (i, x) in self.clean.bytes().enumerate().skip(open) {
+            match x {
+                b'{' => depth += 1,
+                b'}' => {
+                    depth -= 1;
+                    if depth == 0 {
+                        return i + 1;
+                    }
+                }
+                _ => {}
+            }
+        }
+        self.clean.len()
+    }
+
+    /// End of the declaration starting at `start` and where its body starts, if it has one.
+    /// None for the head of a block whose contents are top-level declarations.
+    fn declaration_end(&self, start: usize) -> Option<(usize, Option<usize>)> {
+        let bytes = self.clean.as_bytes();
+        let mut depth = 0usize;
+        let mut body = None;
+        let mut i = start;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'#' if self.at_line_start(i) => i = self.line_end(i),
+                b'(' | b'[' => depth += 1,
+                b')' | b']' => depth = depth.saturating_sub(1),
+                b';' if depth == 0 => return Some((i + 1, body)),
+                b'{' if depth == 0 => {
+                    let head = self.clean[start..i].trim();
+                    if self.transparent.is_match(head) {
+                        return None;
+                    }
+                    let end = self.matching_brace(i);
+                    if body.is_none() && self.is_function(head) {
+                        return Some((end, Some(i)));
+                    }
+                    body.get_or_insert(i);
+                    i = end;
+                    continue;
+                }
+                // A missing `;` before the end of a namespace
+                b'}' if depth == 0 => return Some((i, body)),
+                _ => {}
+            }
+            i += 1;
+        }
+        Some((bytes.len(), body))
+    }
+
+    /// Head of a declaration without its `template <...>` prefixes.
+    fn strip_template<'h>(&self, mut head: &'h str) -> &'h str {
+        while let Some(rest) = head.strip_prefix("template") {
+            let rest = rest.trim_start();
+            if !rest.starts_with('<') {
+                break;
+            }
+            let mut depth = 0;
+            let Some(end) = rest.char_indices().find_map(|(i, x)| {
+                match x {
+                    '<' => depth += 1,
+                    '>' => depth -= 1,
+                    _ => {}
+                }
+                (depth == 0).then_some(i + 1)
+            }) else {
+                break;
+            };
+            head = rest[end..].trim_start();
+        }
+        head
+    }
+
+    fn is_function(&self, head: &str) -> bool {
+        let head = self.strip_template(head);
+        let first = self.ident.find(head).map(|x| x.as_str());
+        if first.is_some_and(|x| CPP_KEYWORDS.contains(&x)) {
+            return false;
+        }
+        let Some(paren) = head.find('(') else {
+            return false;
+        };
+        // Not a variable initialized by a lambda, unless it is `operator=`
+        let name = &head[..paren];
+        name.contains("operator") || !name.contains('=')
+    }
+
+    fn is_main(&self, start: usize, body: Option<usize>) -> bool {
+        let Some(body) = body else {
+            return false;
+        };
+        let head = self.clean[start..body].trim();
+        if !self.is_function(head) {
+            return false;
+        }
+        let paren = head.find('(').unwrap_or(head.len());
+        self.function_name
+            .captures(&head[..paren])
+            .is_some_and(|x| x.get(1).is_none() && &x[2] == "main")
+    }
+
+    fn kind(&self, start: usize, end: usize, body: Option<usize>) -> Kind {
+        let head = self.clean[start..body.unwrap_or(end)].trim();
+        let head = self.strip_template(head);
+
+        if let Some(captures) = self.type_name.captures(head) {
+            let name = captures[1].to_string();
+            if captures.get(2).is_some() {
+                // A specialization is used with the template
+                return self.attached(head, None);
+            }
+            let Some(body) = body else {
+                let rest = &head[captures.get(0).map_or(0, |x| x.end())..];
+                return if rest.trim() == ";" {
+                    // A forward declaration
+                    Kind::Attached(vec![name])
+                } else {
+                    Kind::Root
+                };
+            };
+            let body_end = self.matching_brace(body);
+            if !self.clean[body_end..end]
+                .trim()
+                .trim_end_matches(';')
+                .trim()
+                .is_empty()
+            {
+                // Variables are declared with it
+                return Kind::Root;
+            }
+            if head.starts_with("enum") {
+                // Enumerators are used without the name of the enum
+                let mut names: Vec<_> = self
+                    .idents(&self.clean[body..body_end])
+                    .into_iter()
+                    .collect();
+                names.push(name);
+                return Kind::Named(names);
+            }
+            return Kind::Named(vec![name]);
+        }
+
+        if !self.is_function(head) {
+            return Kind::Root;
+        }
+        let paren = head.find('(').unwrap_or(head.len());
+        let before = &head[..paren];
+        if before.contains("operator") {
+            // Operators go with the types they are for
+            return self.attached(head, None);
+        }
+        let Some(captures) = self.function_name.captures(before) else {
+            return Kind::Root;
+        };
+        let name = captures[2].to_string();
+        if captures.get(3).is_some() {
+            // A specialization is used with the template
+            return self.attached(head, None);
+        }
+        if captures.get(1).is_some() {
+            // A member defined outside of its class
+            return self.attached(before, Some(&name));
+        }
+        match body {
+            _ if name == "main" => Kind::Root,
+            Some(_) => Kind::Named(vec![name]),
+            // A prototype, or a variable constructed with arguments
+            None => Kind::Attached(vec![name]),
+        }
+    }
+
+    /// Kept with the types and functions named in the head.
+    fn attached(&self, head: &str, except: Option<&str>) -> Kind {
+        let owners = self
+            .idents(head)
+            .into_iter()
+            .filter(|x| Some(x.as_str()) != except && x != "operator")
+            .collect();
+        Kind::Attached(owners)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pruned_rust(code: &str) -> String {
+        let (pruned, _) = prune_rust(code).expect("nothing was pruned");
+        invalidate_current_thread_spans();
+        syn::parse_file(&pruned).expect("the pruned code doesn't parse");
+        pruned
+    }
+
+    fn pruned_cpp(code: &str) -> String {
+        prune_cpp(code).expect("nothing was pruned").0
+    }
+
+    fn kind(code: &str) -> Kind {
+        let scanner = CppScanner::new(code);
+        let (end, body) = scanner.declaration_end(0).unwrap();
+        scanner.kind(0, end, body)
+    }
+
+    #[test]
+    fn rust_impls_go_with_their_types() {
+        let code = "\
+struct Used;
+impl Used {
+    fn new() -> Self { Used }
+}
+struct Unused;
+impl Unused {
+    fn new() -> Self { Unused }
+}
+impl std::fmt::Display for Unused {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { Ok(()) }
+}
+fn main() {
+    Used::new();
+}
+";
+        let pruned = pruned_rust(code);
+        assert!(pruned.contains("impl Used"));
+        assert!(!pruned.contains("Unused"));
+    }
+
+    #[test]
+    fn rust_traits_are_used_through_their_methods() {
+        let code = "\
+trait Twice {
+    fn twice(&self) -> Self;
+}
+impl Twice for i32 {
+    fn twice(&self) -> Self { self * 2 }
+}
+trait Never {
+    fn never(&self);
+}
+fn main() {
+    3.twice();
+}
+";
+        let pruned = pruned_rust(code);
+        assert!(pruned.contains("trait Twice"));
+        assert!(pruned.contains("impl Twice for i32"));
+        assert!(!pruned.contains("Never"));
+    }
+
+    #[test]
+    fn rust_use_groups_lose_removed_items() {
+        let code = "\
+mod lib {
+    pub fn used() {}
+    pub fn unused() {}
+    pub fn also_unused() {}
+}
+use lib::{also_unused, used, unused};
+use lib::unused as renamed;
+fn main() {
+    used();
+}
+";
+        let pruned = pruned_rust(code);
+        assert!(pruned.contains("use lib::{ used, };"));
+        assert!(!pruned.contains("unused"));
+        assert!(!pruned.contains("renamed"));
+    }
+
+    #[test]
+    fn rust_without_main_is_left_alone() {
+        assert!(prune_rust("fn unused() {}").is_none());
+        invalidate_current_thread_spans();
+    }
+
+    #[test]
+    fn cpp_templates_and_specializations() {
+        let code = "\
+template <class T> struct Box { T x; };
+template <> struct Box<int> { int x; };
+template <class T> struct Unused { T x; };
+template <> struct Unused<int> { int x; };
+template <class T> T twice(T x) { return x * 2; }
+template <> int twice<int>(int x) { return x + x; }
+int main() {
+    Box<int> b;
+    return twice(b.x);
+}
+";
+        let pruned = pruned_cpp(code);
+        assert!(pruned.contains("struct Box<int>"));
+        assert!(pruned.contains("int twice<int>"));
+        assert!(!pruned.contains("Unused"));
+    }
+
+    #[test]
+    fn cpp_members_defined_out_of_class() {
+        let code = "\
+struct Seg {
+    void build();
+};
+void Seg::build() {}
+struct Unused {
+    void build();
+};
+void Unused::build() {}
+int main() {
+    Seg s;
+    s.build();
+}
+";
+        let pruned = pruned_cpp(code);
+        assert!(pruned.contains("void Seg::build() {}"));
+        assert!(!pruned.contains("Unused"));
+    }
+
+    #[test]
+    fn cpp_types_declaring_variables_are_kept() {
+        let code = "\
+struct S { int x; } s;
+struct Unused { int x; };
+int main() {}
+";
+        let pruned = pruned_cpp(code);
+        assert!(pruned.contains("struct S { int x; } s;"));
+        assert!(!pruned.contains("Unused"));
+        assert!(matches!(kind("struct S { int x; } s;"), Kind::Root));
+    }
+
+    #[test]
+    fn cpp_namespaces_are_looked_into() {
+        let code = "\
+namespace lib {
+int used() { return 1; }
+int unused() { return 2; }
+}
+int main() { return lib::used(); }
+";
+        let pruned = pruned_cpp(code);
+        assert!(pruned.contains("namespace lib {"));
+        assert!(pruned.contains("int used()"));
+        assert!(!pruned.contains("unused"));
+    }
+
+    #[test]
+    fn cpp_raw_strings_and_digit_separators() {
+        let code = "\
+int main() {
+    const char *s = R\"x(} \" int fake() {)x\";
+    int n = 1'000, c = '}';
+}
+int unused() { return 0; }
+";
+        let pruned = pruned_cpp(code);
+        assert!(pruned.contains("int n = 1'000, c = '}';\n}"));
+        assert!(!pruned.contains("unused"));
+    }
+
+    #[test]
+    fn cpp_kinds() {
+        assert!(matches!(kind("struct S;"), Kind::Attached(names) if names == ["S"]));
+        assert!(matches!(kind("int f(int x) { return x; }"), Kind::Named(names) if names == ["f"]));
+        assert!(matches!(kind("int f(int x);"), Kind::Attached(names) if names == ["f"]));
+        assert!(matches!(
+            kind("auto f = [](int x) { return x; };"),
+            Kind::Root
+        ));
+        let Kind::Named(mut names) = kind("enum Color { Red, Green };") else {
+            panic!("an enum is named");
+        };
+        names.sort();
+        assert_eq!(names, ["Color", "Green", "Red"]);
+    }
+
+    #[test]
+    fn blank_comments_keeps_offsets() {
+        let code = "int a; // one\n/* two\nthree */ char *s = \"// é\", c = '\"';\n";
+        let clean = blank_comments(code, false);
+        assert_eq!(clean.len(), code.len());
+        assert_eq!(clean.lines().count(), code.lines().count());
+        assert_eq!(clean.lines().next(), Some("int a;       "));
+        assert!(clean.contains("char *s = \"// é\", c = '\"';"));
+
+        let clean = blank_comments(code, true);
+        assert!(clean.contains("char *s = \"     \", c = ' ';"));
+    }
+}
@@ -0,0 +1,384 @@
+use super::SourceMap;
+use crate::utils::{extract_code_block_with_line, resolve_path};
+use regex::Regex;
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt::Write,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// Installs an import hook that runs the bundled modules from their sources when they are
+/// imported, each in its own module object. They get their own names in `__name__`, so their
+/// `if __name__ == "__main__"` blocks don't run, and their original files in tracebacks.
+const LOADER: &str = r#"
+    class Loader(importlib.abc.MetaPathFinder, importlib.abc.Loader):
+        def find_spec(self, name, path=None, target=None):
+            if name not in modules:
+                return None
+            file, package, _ = modules[name]
+            return importlib.util.spec_from_loader(
+                name, self, origin=file, is_package=package
+            )
+
+        def create_module(self, spec):
+            return None
+
+        def exec_module(self, module):
+            file, _, source = modules[module.__name__]
+            if file is not None:
+                module.__file__ = file
+            exec(compile(source, file or module.__name__, "exec"), module.__dict__)
+
+    sys.meta_path.insert(0, Loader())
+
+
+_cp_assist_bundle()
+del _cp_assist_bundle
+"#;
+
+/// The code with the local modules it imports, directly or through other modules, bundled in
+/// front of it. Modules are looked up like Python would with the directory of the solution
+/// and the directories of `include` in `sys.path`, except that a key of `include` names the
+/// module when its path is a file or a package. The code is returned with where its lines
+/// come from.
+pub fn inline_imports(
+    path: &Path,
+    source: &str,
+    include: &HashMap<String, String>,
+    dir: &Path,
+) -> Result<(String, SourceMap), String> {
+    let (source, start) = extract_code_block_with_line(source);
+    let source = source.as_str();
+    let mut finder = Finder {
+        solution: path.to_path_buf(),
+        named: vec![],
+        roots: path.parent().map(Path::to_path_buf).into_iter().collect(),
+        imports: Imports::new(),
+        seen: HashSet::new(),
+        modules: vec![],
+    };
+    for (name, value) in include.iter().collect::<BTreeMap<_, _>>() {
+        let value = resolve_path(dir, value);
+        if value.is_file() || value.join("__init__.py").is_file() {
+            finder.named.push((name.clone(), value));
+        } else {
+            finder.roots.push(value);
+        }
+    }
+
+    for name in finder.imports.find(source, None) {
+        finder.visit(&name, false)?;
+    }
+    if finder.modules.is_empty() {
+        return Ok((source.into(), SourceMap::new(path, start)));
+    }
+
+    // `from __future__` imports must come first, and the encoding before line 3
+    let lines: Vec<_> = source.lines().collect();
+    let prelude = lines
+        .iter()
+        .take_while(|x| {
+            let x = x.trim();
+            x.is_empty() || x.starts_with('#') || x.starts_with("from __future__ import")
+        })
+        .count();
+
+    let mut code = String::new();
+    for line in &lines[..prelude] {
+        let _ = writeln!(code, "{line}");
+    }
+    code.push_str("def _cp_assist_bundle():\n");
+    code.push_str("    import importlib.abc\n    import importlib.util\n    import sys\n\n");
+    code.push_str("    # Module name: (file, is package, source)\n    modules = {\n");
+    for module in &finder.modules {
+        let _ = writeln!(
+            code,
+            "        {}: ({}, {}, \"\"\"\\\n{}\"\"\"),",
+            quote(&module.name),
+            module
+                .file
+                .as_ref()
+                .map_or("None".into(), |x| quote(&x.to_string_lossy())),
+            if module.package { "True" } else { "False" },
+            escape(&module.source)
+        );
+    }
+    code.push_str("    }\n");
+    code.push_str(LOADER);
+    let mut map = SourceMap::new(path, start);
+    map.push(prelude, None);
+    map.push(code.matches('\n').count(), Some((path, start + prelude)));
+    for line in &lines[prelude..] {
+        let _ = writeln!(code, "{line}");
+    }
+    Ok((code, map))
+}
+
+struct Module {
+    name: String,
+    /// None for a namespace package, a directory without `__init__.py`
+    file: Option<PathBuf>,
+    package: bool,
+    source: String,
+}
+
+struct Finder {
+    solution: PathBuf,
+    /// Modules and packages named by a key of `include`
+    named: Vec<(String, PathBuf)>,
+    /// Directories searched for modules
+    roots: Vec<PathBuf>,
+    imports: Imports,
+    seen: HashSet<String>,
+    /// Each module after the ones it imports
+    modules: Vec<Module>,
+}
+
+impl Finder {
+    /// File of the module and whether it is a package, if it can be found locally.
+    fn find(&self, name: &str) -> Option<(PathBuf, bool)> {
+        let mut parts = name.split('.');
+        let top = parts.next()?;
+        let rest: Vec<_> = parts.collect();
+
+        let named = self
+            .named
+            .iter()
+            .filter(|(key, _)| key == top)
+            .map(|(_, path)| path.clone());
+        named
+            .chain(self.roots.iter().map(|x| x.join(top)))
+            .find_map(|base| {
+                let path = rest.iter().fold(base, |path, x| path.join(x));
+                if path.is_file() {
+                    return Some((path, false));
+                }
+                let init = path.join("__init__.py");
+                if init.is_file() {
+                    return Some((init, true));
+                }
+                let mut file = path.into_os_string();
+                file.push(".py");
+                let file = PathBuf::from(file);
+                file.is_file().then_some((file, false))
+            })
+    }
+
+    fn visit(&mut self, name: &str, parent: bool) -> Result<(), String> {
+        if self.seen.contains(name) {
+            return Ok(());
+        }
+        let (file, package) = match self.find(name) {
+            Some((file, package)) => (Some(file), package),
+            // Only a directory on the way to a module is taken for a namespace package
+            None if parent => (None, true),
+            None => return Ok(()),
+        };
+        if file.as_ref() == Some(&self.solution) {
+            return Ok(());
+        }
+        self.seen.insert(name.into());
+
+        // Importing a module imports the packages it is in first
+        if let Some((parent, _)) = name.rsplit_once('.') {
+            self.visit(parent, true)?;
+        }
+
+        let source = match &file {
+            Some(file) => {
+                let (source, start) = extract_code_block_with_line(
+                    &fs::read_to_string(file)
+                        .map_err(|e| format!("Failed to read file {:?}: {}", file, e))?,
+                );
+                // Tracebacks then give the lines of the file
+                "\n".repeat(start) + source.as_str()
+            }
+            None => String::new(),
+        };
+        let package_name = if package {
+            Some(name)
+        } else {
+            name.rsplit_once('.').map(|(parent, _)| parent)
+        };
+        for import in self.imports.find(&source, package_name) {
+            self.visit(&import, false)?;
+        }
+
+        self.modules.push(Module {
+            name: name.into(),
+            file,
+            package,
+            source,
+        });
+        Ok(())
+    }
+}
+
+/// Finds the modules imported by a source.
+struct Imports {
+    import: Regex,
+    from: Regex,
+}
+
+impl Imports {
+    fn new() -> Self {
+        Imports {
+            import: Regex::new(r"^\s*import\s+(.+)$").unwrap(),
+            from: Regex::new(r"^\s*from\s+(\.*)\s*([\w.]*)\s+import\s+(.+)$").unwrap(),
+        }
+    }
+
+    /// Absolute names of the imported modules, and of the names imported from them that may
+    /// be modules too. Relative imports are resolved in the package, if the source is in one.
+    fn find(&self, source: &str, package: Option<&str>) -> Vec<String> {
+        let mut modules = vec![];
+        let mut lines = source.lines();
+        while let Some(line) = lines.next() {
+            let mut statement = line.split('#').next().unwrap_or_default().to_string();
+            // Continued by a backslash or inside parentheses
+            while statement.trim_end().ends_with('\\')
+                || statement.matches('(').count() > statement.matches(')').count()
+            {
+                let Some(next) = lines.next() else {
+                    break;
+                };
+                statement = statement.trim_end().trim_end_matches('\\').to_string();
+                statement.push(' ');
+                statement.push_str(next.split('#').next().unwrap_or_default());
+            }
+
+            for statement in statement.split(';') {
+                if let Some(captures) = self.import.captures(statement) {
+                    modules.extend(names(&captures[1]));
+                } else if let Some(captures) = self.from.captures(statement) {
+                    let level = captures[1].len();
+                    let Some(module) = absolute(&captures[2], level, package) else {
+                        continue;
+                    };
+                    for name in names(&captures[3]) {
+                        modules.push(format!("{module}.{name}"));
+                    }
+                    modules.push(module);
+                }
+            }
+        }
+        modules
+    }
+}
+
+/// Names in a list like `a.b as c, d`, without the aliases and parentheses.
+fn names(list: &str) -> Vec<String> {
+    list.split(',')
+        .filter_map(|x| {
+            let name = x.trim_matches(|c: char| c.is_whitespace() || c == '(' || c == ')');
+            let name = name.split_whitespace().next()?;
+            (name != "*").then(|| name.to_string())
+        })
+        .collect()
+}
+
+/// Absolute name of a module imported `level` packages up, as in `from ..a import b`.
+fn absolute(module: &str, level: usize, package: Option<&str>) -> Option<String> {
+    if level == 0 {
+        return (!module.is_empty()).then(|| module.into());
+    }
+    let mut base = package?;
+    for _ in 1..level {
+        base = base.rsplit_once('.')?.0;
+    }
+    Some(if module.is_empty() {
+        base.into()
+    } else {
+        format!("{base}.{module}")
+    })
+}
+
+fn quote(text: &str) -> String {
+    format!(
+        "\"{}\"",
+        text.replace('\\', "\\\\")
+            .replace('"', "\\\"")
+            .replace('\n', "\\n")
+            .replace('\r', "\\r")
+    )
+}
+
+/// Source to put in a triple-quoted string, ending with a newline.
+fn escape(source: &str) -> String {
+    let mut escaped = source
+        .replace('\\', "\\\\")
+        .replace("\"\"\"", "\\\"\\\"\\\"");
+    if !escaped.ends_with('\n') {
+        escaped.push('\n');
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::write_files;
+
+    #[test]
+    fn imports_across_lines() {
+        let source = "\
+import a, b.c as d
+from e import (f,
+    g)  # a comment
+from h \\
+    import i
+import j; from . import k
+# import nope
+";
+        let found = Imports::new().find(source, None);
+        assert_eq!(found, ["a", "b.c", "e.f", "e.g", "e", "h.i", "h", "j"]);
+    }
+
+    #[test]
+    fn relative_imports() {
+        let source = "from . import x\nfrom ..y import z\n";
+        let found = Imports::new().find(source, Some("pkg.sub"));
+        assert_eq!(found, ["pkg.sub.x", "pkg.sub", "pkg.y.z", "pkg.y"]);
+    }
+
+    #[test]
+    fn absolute_names() {
+        assert_eq!(absolute("a", 0, None), Some("a".into()));
+        assert_eq!(absolute("", 0, Some("p")), None);
+        assert_eq!(absolute("b", 1, Some("p.q")), Some("p.q.b".into()));
+        assert_eq!(absolute("", 2, Some("p.q")), Some("p".into()));
+        assert_eq!(absolute("", 3, Some("p.q")), None);
+        assert_eq!(absolute("b", 1, None), None);
+    }
+
+    #[test]
+    fn escape_triple_quotes() {
+        let source = r#"s = """x"""
+print("\n")"#;
+        let expected = r#"s = \"\"\"x\"\"\"
+print("\\n")
+"#;
+        assert_eq!(escape(source), expected);
+    }
+
+    #[test]
+    fn modules_follow_their_imports() {
+        let dir = write_files(&[
+            ("pkg/__init__.py", "from .util import helper\n"),
+            ("pkg/util.py", "import lib\ndef helper(): pass\n"),
+            ("lib.py", "X = 1\n"),
+        ]);
+        let source = "from __future__ import annotations\nimport pkg\nimport sys\n";
+        let result = inline_imports(&dir.join("main.py"), source, &HashMap::new(), &dir);
+        fs::remove_dir_all(&dir).unwrap();
+
+        let (code, _) = result.unwrap();
+        assert!(code.starts_with("from __future__ import annotations\ndef _cp_assist_bundle():"));
+        let position = |name: &str| code.find(&format!("        \"{name}\": (")).unwrap();
+        assert!(position("lib") < position("pkg.util"));
+        assert!(position("pkg.util") < position("pkg"));
+        assert!(!code.contains("\"sys\""));
+        assert!(code.ends_with("import pkg\nimport sys\n"));
+    }
+}
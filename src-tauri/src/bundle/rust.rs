@@ -0,0 +1,550 @@
+use super::{apply, Offsets, SourceMap};
+use crate::utils::{extract_code_block_with_line, resolve_path};
+use proc_macro2::{extra::invalidate_current_thread_spans, LineColumn, TokenStream, TokenTree};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+};
+use syn::{
+    spanned::Spanned,
+    visit::{self, Visit},
+    Attribute, Expr, Item, ItemMod, ItemUse, Lit, Meta, UseTree,
+};
+
+/// Modules of the library used by the solution, directly or through other modules, in the
+/// order of their names. Their `mod` declarations are inlined, and `crate::` paths to their
+/// own items are rewritten to go through the module, as it is put under the crate root.
+pub fn get_rust_modules(
+    path: &Path,
+    source: &str,
+    include: &HashMap<String, String>,
+    dir: &Path,
+) -> Result<Vec<(String, String, SourceMap)>, String> {
+    let roots = find_roots(include, dir);
+    let names = roots.keys().cloned().collect();
+    let result = bundle(path, source, &roots, &names);
+    // The spans of every parsed file are kept until then
+    invalidate_current_thread_spans();
+    result
+}
+
+fn bundle(
+    path: &Path,
+    source: &str,
+    roots: &BTreeMap<String, PathBuf>,
+    names: &BTreeSet<String>,
+) -> Result<Vec<(String, String, SourceMap)>, String> {
+    let mut solution = Collector::new(names);
+    solution.visit_file(&parse(path, source)?);
+
+    let mut queue: Vec<_> = solution.uses.into_iter().collect();
+    let mut modules = BTreeMap::new();
+    while let Some(name) = queue.pop() {
+        if modules.contains_key(&name) {
+            continue;
+        }
+        let root = &roots[&name];
+        let mut module = Module {
+            name: &name,
+            names,
+            uses: BTreeSet::new(),
+            expanding: HashSet::new(),
+        };
+        let (code, map) = module.expand(root, &children_dir(root, false))?;
+        queue.extend(module.uses);
+        modules.insert(name, (code, map));
+    }
+
+    Ok(modules
+        .into_iter()
+        .map(|(name, (code, map))| (name, code, map))
+        .collect())
+}
+
+/// Root file of every module that can be bundled. A directory is a module when it has a
+/// `mod.rs` or `lib.rs`, otherwise each of its files and module directories is one.
+fn find_roots(include: &HashMap<String, String>, dir: &Path) -> BTreeMap<String, PathBuf> {
+    let mut roots = BTreeMap::new();
+    for (name, path) in include {
+        let path = resolve_path(dir, path);
+        if path.is_file() {
+            roots.insert(name.clone(), path);
+            continue;
+        }
+        if let Some(root) = dir_root(&path) {
+            roots.insert(name.clone(), root);
+            continue;
+        }
+        let Ok(entries) = path.read_dir() else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let root = if path.is_dir() {
+                dir_root(&path)
+            } else {
+                path.extension()
+                    .is_some_and(|x| x == "rs")
+                    .then(|| path.clone())
+            };
+            if let (Some(stem), Some(root)) = (path.file_stem(), root) {
+                roots.insert(stem.to_string_lossy().into(), root);
+            }
+        }
+    }
+    roots
+}
+
+fn dir_root(dir: &Path) -> Option<PathBuf> {
+    ["mod.rs", "lib.rs"]
+        .into_iter()
+        .map(|x| dir.join(x))
+        .find(|x| x.is_file())
+}
+
+/// Directory of the modules declared in a file: its own for `mod.rs`, `lib.rs` and files
+/// given by a `#[path]`, and one named after it for any other file.
+fn children_dir(file: &Path, from_path_attr: bool) -> PathBuf {
+    let parent = file.parent().unwrap_or(Path::new("")).to_path_buf();
+    let stem = file.file_stem().unwrap_or_default();
+    if from_path_attr || stem == "mod" || stem == "lib" {
+        parent
+    } else {
+        parent.join(stem)
+    }
+}
+
+fn parse(path: &Path, source: &str) -> Result<syn::File, String> {
+    syn::parse_file(source).map_err(|e| {
+        let start = e.span().start();
+        format!(
+            "Failed to parse {}:{}:{}: {}",
+            path.display(),
+            start.line,
+            start.column + 1,
+            e
+        )
+    })
+}
+
+/// A module of the library being put together from its files.
+struct Module<'a> {
+    name: &'a str,
+    names: &'a BTreeSet<String>,
+    /// Other modules of the library it uses
+    uses: BTreeSet<String>,
+    /// Files being expanded, to catch a file that declares itself
+    expanding: HashSet<PathBuf>,
+}
+
+impl Module<'_> {
+    /// Code of the file with its `mod` declarations replaced by the expanded files, and where
+    /// its lines come from.
+    fn expand(&mut self, file: &Path, dir: &Path) -> Result<(String, SourceMap), String> {
+        if !self.expanding.insert(file.to_path_buf()) {
+            return Err(format!("{} declares itself as a module", file.display()));
+        }
+        let source = fs::read_to_string(file)
+            .map_err(|e| format!("Failed to read file {:?}: {}", file, e))?;
+        let (source, start) = extract_code_block_with_line(source.trim_start_matches('\u{feff}'));
+
+        let mut collector = Collector::new(self.names);
+        collector.visit_file(&parse(file, &source)?);
+        self.uses.extend(collector.uses);
+
+        let offsets = Offsets::new(&source);
+        let mut edits = vec![];
+        for at in collector.crate_paths {
+            let at = offsets.get(at);
+            edits.push((at..at, format!("{}::", self.name)));
+        }
+        // Removed items leave their lines, so the lines after them keep their numbers
+        for range in collector.removed {
+            let range = offsets.range(range);
+            let lines = source[range.clone()].matches('\n').count();
+            edits.push((range, "\n".repeat(lines)));
+        }
+        let mut children = vec![];
+        for decl in collector.mods {
+            let base = decl.inline.iter().fold(dir.to_path_buf(), |x, m| x.join(m));
+            let (child, child_dir) = match &decl.path {
+                Some(path) if decl.inline.is_empty() => {
+                    let child = file.parent().unwrap_or(Path::new("")).join(path);
+                    let child_dir = children_dir(&child, true);
+                    (child, child_dir)
+                }
+                Some(path) => {
+                    let child = base.join(path);
+                    let child_dir = children_dir(&child, true);
+                    (child, child_dir)
+                }
+                None => {
+                    let candidates = [
+                        base.join(format!("{}.rs", decl.ident)),
+                        base.join(&decl.ident).join("mod.rs"),
+                    ];
+                    let child = candidates.iter().find(|x| x.is_file()).ok_or_else(|| {
+                        format!(
+                            "File of module `{}` declared in {} not found, looked for {:?}",
+                            decl.ident,
+                            file.display(),
+                            candidates
+                        )
+                    })?;
+                    (child.clone(), base.join(&decl.ident))
+                }
+            };
+            let (code, map) = self.expand(&child, &child_dir)?;
+            children.push((
+                decl.semi.start.line - 1,
+                code.matches('\n').count() + 1,
+                map,
+            ));
+            edits.push((offsets.range(decl.semi), format!(" {{\n{code}\n}}")));
+        }
+
+        // The lines of the file up to a declaration, the lines of the module on the next
+        // ones, and the rest of the line of the declaration after its closing brace
+        children.sort_by_key(|(line, _, _)| *line);
+        let mut map = SourceMap::default();
+        let (mut line, mut next) = (0, 0);
+        for (decl_line, lines, child) in children {
+            map.push(line, Some((file, start + next)));
+            line += decl_line - next + 1;
+            map.embed(line, &child);
+            line += lines;
+            next = decl_line;
+        }
+        map.push(line, Some((file, start + next)));
+
+        self.expanding.remove(file);
+        Ok((apply(&source, edits), map))
+    }
+}
+
+/// A `mod x;` declaration, whose file is looked up.
+struct ModDecl {
+    ident: String,
+    /// Inline modules the declaration is in
+    inline: Vec<String>,
+    /// Value of a `#[path]` attribute
+    path: Option<String>,
+    semi: Range<LineColumn>,
+}
+
+/// Walks a file for the library modules it uses, and the places that need changes when it
+/// is bundled.
+struct Collector<'a> {
+    names: &'a BTreeSet<String>,
+    uses: BTreeSet<String>,
+    /// Where `crate::` is followed by an item of the module itself
+    crate_paths: Vec<LineColumn>,
+    mods: Vec<ModDecl>,
+    /// Items only compiled for tests
+    removed: Vec<Range<LineColumn>>,
+    inline: Vec<String>,
+}
+
+impl<'a> Collector<'a> {
+    fn new(names: &'a BTreeSet<String>) -> Self {
+        Collector {
+            names,
+            uses: BTreeSet::new(),
+            crate_paths: vec![],
+            mods: vec![],
+            removed: vec![],
+            inline: vec![],
+        }
+    }
+
+    /// Handles the segment following `crate::`.
+    fn after_crate(&mut self, ident: &syn::Ident) {
+        let name = ident.to_string();
+        if self.names.contains(&name) {
+            self.uses.insert(name);
+        } else {
+            self.crate_paths.push(ident.span().start());
+        }
+    }
+
+    fn first_segment(&mut self, ident: &syn::Ident) {
+        let name = ident.to_string();
+        if self.names.contains(&name) {
+            self.uses.insert(name);
+        }
+    }
+
+    fn use_tree(&mut self, tree: &UseTree, after_crate: bool) {
+        match tree {
+            UseTree::Path(path) if !after_crate && path.ident == "crate" => {
+                self.use_tree(&path.tree, true)
+            }
+            UseTree::Path(path)
+                if !after_crate && (path.ident == "self" || path.ident == "super") =>
+            {
+                self.use_tree(&path.tree, false)
+            }
+            UseTree::Path(path) if after_crate => self.after_crate(&path.ident),
+            UseTree::Path(path) => self.first_segment(&path.ident),
+            UseTree::Name(name) if after_crate => self.after_crate(&name.ident),
+            UseTree::Name(name) => self.first_segment(&name.ident),
+            UseTree::Rename(rename) if after_crate => self.after_crate(&rename.ident),
+            UseTree::Rename(rename) => self.first_segment(&rename.ident),
+            UseTree::Glob(glob) if after_crate => {
+                self.crate_paths.push(glob.star_token.span.start())
+            }
+            UseTree::Glob(_) => {}
+            UseTree::Group(group) => {
+                for tree in &group.items {
+                    self.use_tree(tree, after_crate);
+                }
+            }
+        }
+    }
+
+    /// Macros aren't parsed, so their tokens are searched for `crate::x` and paths starting
+    /// with a library name, as in `x::`.
+    fn tokens(&mut self, tokens: TokenStream) {
+        let tokens: Vec<_> = tokens.into_iter().collect();
+        let path_sep = |i: usize| {
+            matches!(
+                tokens.get(i..i + 2),
+                Some([TokenTree::Punct(a), TokenTree::Punct(b)])
+                    if a.as_char() == ':' && b.as_char() == ':'
+            )
+        };
+        for (i, token) in tokens.iter().enumerate() {
+            match token {
+                TokenTree::Group(group) => self.tokens(group.stream()),
+                TokenTree::Ident(ident) if ident == "crate" => {
+                    if let (true, Some(TokenTree::Ident(next))) =
+                        (path_sep(i + 1), tokens.get(i + 3))
+                    {
+                        self.after_crate(next);
+                    }
+                }
+                TokenTree::Ident(ident) if path_sep(i + 1) => {
+                    // Only the first segment, or the one after `self::` and `super::`
+                    let first = i < 2
+                        || !path_sep(i - 2)
+                        || matches!(
+                            i.checked_sub(3).map(|j| &tokens[j]),
+                            Some(TokenTree::Ident(x)) if x == "self" || x == "super"
+                        );
+                    if first {
+                        self.first_segment(ident);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+fn is_test_only(attrs: &[Attribute]) -> bool {
+    attrs.iter().any(|attr| {
+        attr.path().is_ident("cfg") && attr.parse_args::<syn::Ident>().is_ok_and(|x| x == "test")
+    })
+}
+
+fn path_attr(attrs: &[Attribute]) -> Option<String> {
+    attrs.iter().find_map(|attr| match &attr.meta {
+        Meta::NameValue(meta) if meta.path.is_ident("path") => match &meta.value {
+            Expr::Lit(expr) => match &expr.lit {
+                Lit::Str(path) => Some(path.value()),
+                _ => None,
+            },
+            _ => None,
+        },
+        _ => None,
+    })
+}
+
+impl<'ast> Visit<'ast> for Collector<'_> {
+    fn visit_item(&mut self, item: &'ast Item) {
+        let attrs = match item {
+            Item::Const(x) => &x.attrs,
+            Item::Enum(x) => &x.attrs,
+            Item::Fn(x) => &x.attrs,
+            Item::Impl(x) => &x.attrs,
+            Item::Macro(x) => &x.attrs,
+            Item::Mod(x) => &x.attrs,
+            Item::Static(x) => &x.attrs,
+            Item::Struct(x) => &x.attrs,
+            Item::Trait(x) => &x.attrs,
+            Item::Type(x) => &x.attrs,
+            Item::Use(x) => &x.attrs,
+            _ => return visit::visit_item(self, item),
+        };
+        if is_test_only(attrs) {
+            let span = item.span();
+            self.removed.push(span.start()..span.end());
+        } else {
+            visit::visit_item(self, item);
+        }
+    }
+
+    fn visit_item_mod(&mut self, item: &'ast ItemMod) {
+        match (&item.content, &item.semi) {
+            (None, Some(semi)) => self.mods.push(ModDecl {
+                ident: item.ident.to_string(),
+                inline: self.inline.clone(),
+                path: path_attr(&item.attrs),
+                semi: semi.span.start()..semi.span.end(),
+            }),
+            _ => {
+                self.inline.push(item.ident.to_string());
+                visit::visit_item_mod(self, item);
+                self.inline.pop();
+            }
+        }
+    }
+
+    fn visit_item_use(&mut self, item: &'ast ItemUse) {
+        if item.leading_colon.is_none() {
+            self.use_tree(&item.tree, false);
+        }
+    }
+
+    fn visit_path(&mut self, path: &'ast syn::Path) {
+        if path.leading_colon.is_none() {
+            let mut segments = path
+                .segments
+                .iter()
+                .skip_while(|x| x.ident == "self" || x.ident == "super");
+            match (segments.next(), segments.next()) {
+                (Some(first), Some(second)) if first.ident == "crate" => {
+                    self.after_crate(&second.ident)
+                }
+                // A module is only named by a path going on into it
+                (Some(first), Some(_)) => self.first_segment(&first.ident),
+                _ => {}
+            }
+        }
+        visit::visit_path(self, path);
+    }
+
+    fn visit_macro(&mut self, mac: &'ast syn::Macro) {
+        self.tokens(mac.tokens.clone());
+        visit::visit_macro(self, mac);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bundle::write_files;
+
+    fn names(names: &[&str]) -> BTreeSet<String> {
+        names.iter().map(ToString::to_string).collect()
+    }
+
+    fn collect(names: &BTreeSet<String>, code: &str) -> (BTreeSet<String>, usize) {
+        let mut collector = Collector::new(names);
+        collector.visit_file(&syn::parse_file(code).unwrap());
+        let found = (collector.uses, collector.crate_paths.len());
+        invalidate_current_thread_spans();
+        found
+    }
+
+    #[test]
+    fn collector_finds_used_modules() {
+        let names = names(&["a", "b", "c", "d", "e", "f"]);
+        let code = "
+use crate::{a, b::X};
+use self::c::*;
+fn main() {
+    let e = d::new();
+    println!(\"{}\", e);
+    f();
+}
+";
+        let (uses, crate_paths) = collect(&names, code);
+        assert_eq!(uses, self::names(&["a", "b", "c", "d"]));
+        assert_eq!(crate_paths, 0);
+    }
+
+    #[test]
+    fn collector_scans_macro_tokens() {
+        let names = names(&["a", "b", "c"]);
+        let code = "
+fn main() {
+    println!(\"{} {}\", a::x(), crate::b::y());
+    vec![c, other::a::z()];
+}
+";
+        let (uses, _) = collect(&names, code);
+        assert_eq!(uses, self::names(&["a", "b"]));
+    }
+
+    #[test]
+    fn collector_marks_paths_to_own_items() {
+        let names = names(&["a"]);
+        let code = "
+use crate::{a::X, own::Y};
+use crate::*;
+fn f() -> usize {
+    crate::helper() + crate::a::g()
+}
+";
+        let (uses, crate_paths) = collect(&names, code);
+        assert_eq!(uses, self::names(&["a"]));
+        assert_eq!(crate_paths, 3);
+    }
+
+    #[test]
+    fn children_dirs() {
+        assert_eq!(children_dir(Path::new("a/mod.rs"), false), Path::new("a"));
+        assert_eq!(children_dir(Path::new("a/lib.rs"), false), Path::new("a"));
+        assert_eq!(children_dir(Path::new("a/b.rs"), false), Path::new("a/b"));
+        assert_eq!(children_dir(Path::new("a/b.rs"), true), Path::new("a"));
+    }
+
+    #[test]
+    fn bundles_nested_modules() {
+        let dir = write_files(&[
+            (
+                "lib/seg/mod.rs",
+                "pub mod tree;
+#[path = \"extra_impl.rs\"]
+mod extra;
+pub fn f() -> usize {
+    crate::helper() + tree::g()
+}
+pub fn helper() -> usize {
+    1
+}
+#[cfg(test)]
+mod tests;
+",
+            ),
+            (
+                "lib/seg/tree.rs",
+                "mod leaf;
+pub fn g() -> usize {
+    crate::dsu::h()
+}
+",
+            ),
+            ("lib/seg/tree/leaf.rs", "pub fn leaf() {}\n"),
+            ("lib/seg/extra_impl.rs", "pub fn extra() {}\n"),
+            ("lib/dsu.rs", "pub fn h() -> usize {\n    2\n}\n"),
+            ("lib/unused.rs", "pub fn unused() {}\n"),
+        ]);
+        let include = HashMap::from([("lib".to_string(), "./lib".to_string())]);
+        let source = "use crate::seg::f;\nfn main() {\n    f();\n}\n";
+        let modules = get_rust_modules(&dir.join("main.rs"), source, &include, &dir).unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+
+        let names: Vec<_> = modules.iter().map(|(name, _, _)| name.as_str()).collect();
+        assert_eq!(names, ["dsu", "seg"]);
+        let seg = &modules[1].1;
+        assert!(seg.contains("crate::seg::helper()"));
+        assert!(seg.contains("pub mod tree {\nmod leaf {\npub fn leaf() {}"));
+        assert!(seg.contains("crate::dsu::h()"));
+        assert!(seg.contains("mod extra {\npub fn extra() {}"));
+        assert!(!seg.contains("mod tests"));
+    }
+}
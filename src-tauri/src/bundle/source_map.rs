@@ -0,0 +1,232 @@
+use regex::{Captures, Regex};
+use std::{
+    ops::Range,
+    path::{Path, PathBuf},
+};
+
+/// Where the lines of some code come from, to point diagnostics about the final code at the
+/// files that were put together into it.
+#[derive(Debug, Clone, Default)]
+pub struct SourceMap {
+    /// Line of the code each segment starts at, and the file and line it comes from, None for
+    /// lines that were generated. A segment goes on until the next one.
+    segments: Vec<(usize, Option<(PathBuf, usize)>)>,
+}
+
+impl SourceMap {
+    /// Code made of the lines of a file from `line` on.
+    pub fn new(file: &Path, line: usize) -> Self {
+        SourceMap {
+            segments: vec![(0, Some((file.to_path_buf(), line)))],
+        }
+    }
+
+    /// Lines from `line` of the code on come from `origin`.
+    pub fn push(&mut self, line: usize, origin: Option<(&Path, usize)>) {
+        while self
+            .segments
+            .last()
+            .is_some_and(|(start, _)| *start >= line)
+        {
+            self.segments.pop();
+        }
+        let origin = origin.map(|(file, line)| (file.to_path_buf(), line));
+        if self.origin(line) != origin {
+            self.segments.push((line, origin));
+        }
+    }
+
+    /// Lines from `line` of the code on are those of `other`.
+    pub fn embed(&mut self, line: usize, other: &SourceMap) {
+        self.push(line, None);
+        for (start, origin) in &other.segments {
+            let origin = origin.as_ref().map(|(file, line)| (file.as_path(), *line));
+            self.push(line + start, origin);
+        }
+    }
+
+    /// File and line a line of the code comes from.
+    fn origin(&self, line: usize) -> Option<(PathBuf, usize)> {
+        let index = self.segments.partition_point(|(start, _)| *start <= line);
+        let (start, origin) = self.segments.get(index.checked_sub(1)?)?;
+        let (file, first) = origin.as_ref()?;
+        Some((file.clone(), first + line - start))
+    }
+
+    /// Map of code assembled by a template from pieces, which are looked up in it. Lines of
+    /// the template itself and of pieces it changed aren't mapped.
+    pub fn assemble(code: &str, pieces: &[(&str, &SourceMap)]) -> Self {
+        let mut found: Vec<(Range<usize>, &SourceMap)> = vec![];
+        for (text, map) in pieces {
+            if text.is_empty() {
+                continue;
+            }
+            let start = code.match_indices(text).map(|(i, _)| i).find(|&i| {
+                found
+                    .iter()
+                    .all(|(x, _)| i + text.len() <= x.start || x.end <= i)
+            });
+            if let Some(start) = start {
+                found.push((start..start + text.len(), map));
+            }
+        }
+        found.sort_by_key(|(range, _)| range.start);
+
+        let mut assembled = SourceMap::default();
+        for (range, map) in found {
+            let line = lines_before(code, range.start);
+            assembled.embed(line, map);
+            assembled.push(lines_before(code, range.end) + 1, None);
+        }
+        assembled
+    }
+
+    /// Map of the code once the ranges are removed from it.
+    pub fn without(&self, code: &str, removed: &[Range<usize>]) -> Self {
+        let mut removed = removed.to_vec();
+        removed.sort_by_key(|x| x.start);
+        let mut removed = removed.into_iter().peekable();
+
+        // Line of the code each line is left from, the one of its first byte
+        let mut lines = vec![];
+        let mut line = 0;
+        let mut line_start = true;
+        for (i, byte) in code.bytes().enumerate() {
+            while removed.next_if(|x| x.end <= i).is_some() {}
+            if removed.peek().is_none_or(|x| i < x.start) {
+                if line_start {
+                    lines.push(line);
+                }
+                line_start = byte == b'\n';
+            }
+            if byte == b'\n' {
+                line += 1;
+            }
+        }
+        if line_start {
+            lines.push(line);
+        }
+
+        let mut map = SourceMap::default();
+        for (i, line) in lines.into_iter().enumerate() {
+            let origin = self.origin(line);
+            map.push(
+                i,
+                origin.as_ref().map(|(file, line)| (file.as_path(), *line)),
+            );
+        }
+        map
+    }
+
+    /// Pattern of the places in `source_file` that `rewrite` replaces, as `file:line:col` like
+    /// compilers and panics give them, and as `File "file", line n` in Python tracebacks.
+    pub fn places(source_file: &str) -> Regex {
+        Regex::new(&format!(
+            r#"(?m)(^|[\s"'(<\[])((?:[^\s"'()<>\[\]]*[/\\])?{})(?::(\d+)|(", line )(\d+))"#,
+            regex::escape(source_file)
+        ))
+        .unwrap()
+    }
+
+    /// Diagnostics with the places matched by `places` replaced by the places they come from.
+    /// Places on generated lines are kept.
+    pub fn rewrite(&self, text: &str, places: &Regex) -> String {
+        places
+            .replace_all(text, |captures: &Captures| {
+                let line = captures.get(3).or(captures.get(5)).map(|x| x.as_str());
+                let origin = line
+                    .and_then(|x| x.parse::<usize>().ok())
+                    .and_then(|x| x.checked_sub(1))
+                    .and_then(|x| self.origin(x));
+                let Some((file, line)) = origin else {
+                    return captures[0].to_string();
+                };
+                match captures.get(4) {
+                    Some(python) => format!(
+                        "{}{}{}{}",
+                        &captures[1],
+                        file.display(),
+                        python.as_str(),
+                        line + 1
+                    ),
+                    None => format!("{}{}:{}", &captures[1], file.display(), line + 1),
+                }
+            })
+            .into_owned()
+    }
+}
+
+fn lines_before(code: &str, at: usize) -> usize {
+    code[..at].matches('\n').count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn origin(map: &SourceMap, line: usize) -> Option<(String, usize)> {
+        map.origin(line)
+            .map(|(file, line)| (file.display().to_string(), line))
+    }
+
+    #[test]
+    fn assemble_finds_pieces() {
+        let code_map = SourceMap::new(Path::new("sol.rs"), 2);
+        let lib_map = SourceMap::new(Path::new("lib.rs"), 0);
+        let code = "// header\nfn main() {\n    f();\n}\n\nmod lib {\n    pub fn f() {}\n}\n";
+        let pieces = [
+            ("fn main() {\n    f();\n}", &code_map),
+            ("pub fn f() {}", &lib_map),
+        ];
+        let map = SourceMap::assemble(code, &pieces);
+
+        assert_eq!(origin(&map, 0), None);
+        assert_eq!(origin(&map, 1), Some(("sol.rs".into(), 2)));
+        assert_eq!(origin(&map, 3), Some(("sol.rs".into(), 4)));
+        assert_eq!(origin(&map, 4), None);
+        assert_eq!(origin(&map, 5), None);
+        assert_eq!(origin(&map, 6), Some(("lib.rs".into(), 0)));
+        assert_eq!(origin(&map, 7), None);
+    }
+
+    #[test]
+    fn assemble_skips_missing_pieces() {
+        let map = SourceMap::new(Path::new("sol.rs"), 0);
+        let assembled = SourceMap::assemble("changed\n", &[("original", &map)]);
+        assert_eq!(origin(&assembled, 0), None);
+    }
+
+    #[test]
+    fn without_follows_removed_lines() {
+        let map = SourceMap::new(Path::new("sol.rs"), 10);
+        let code = "a\nb\nc\nd\n";
+        // Removes `b\n` and the `c` of `c\n`, which leaves its newline
+        let without = map.without(code, &[4..5, 2..4]);
+
+        assert_eq!(origin(&without, 0), Some(("sol.rs".into(), 10)));
+        assert_eq!(origin(&without, 1), Some(("sol.rs".into(), 12)));
+        assert_eq!(origin(&without, 2), Some(("sol.rs".into(), 13)));
+    }
+
+    #[test]
+    fn rewrite_maps_places() {
+        let mut map = SourceMap::new(Path::new("/lib/seg.rs"), 4);
+        map.push(2, None);
+        let places = SourceMap::places("main.rs");
+
+        assert_eq!(
+            map.rewrite("error at main.rs:2:7 and --> /tmp/x/main.rs:1:1", &places),
+            "error at /lib/seg.rs:6:7 and --> /lib/seg.rs:5:1"
+        );
+        assert_eq!(
+            map.rewrite(r#"  File "/tmp/x/main.rs", line 1, in f"#, &places),
+            r#"  File "/lib/seg.rs", line 5, in f"#
+        );
+        // Generated lines and other files are kept
+        assert_eq!(map.rewrite("main.rs:3:1", &places), "main.rs:3:1");
+        assert_eq!(
+            map.rewrite("other_main.rs:1:1", &places),
+            "other_main.rs:1:1"
+        );
+    }
+}
@@ -0,0 +1,100 @@
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// How the output of a solution is compared with the expected answer.
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum Comparator {
+    /// Byte for byte
+    Exact,
+    /// Line by line, ignoring surrounding whitespace on each line and around the output
+    #[default]
+    Lines,
+    /// Whitespace separated tokens
+    Tokens,
+    /// Whitespace separated tokens, ignoring ASCII case (YES/yes)
+    TokensIgnoreCase,
+    /// Tokens, where numbers may differ by an absolute or relative error of `epsilon`
+    Float,
+}
+
+impl Comparator {
+    pub fn compare(&self, output: &str, answer: &str, epsilon: f64) -> bool {
+        match self {
+            Comparator::Exact => output == answer,
+            Comparator::Lines => output
+                .trim()
+                .split('\n')
+                .map(|x| x.trim())
+                .eq(answer.trim().split('\n').map(|x| x.trim())),
+            Comparator::Tokens => output.split_whitespace().eq(answer.split_whitespace()),
+            Comparator::TokensIgnoreCase => {
+                let mut answer = answer.split_whitespace();
+                output
+                    .split_whitespace()
+                    .all(|x| answer.next().is_some_and(|y| x.eq_ignore_ascii_case(y)))
+                    && answer.next().is_none()
+            }
+            Comparator::Float => {
+                let mut answer = answer.split_whitespace();
+                output
+                    .split_whitespace()
+                    .all(|x| answer.next().is_some_and(|y| float_eq(x, y, epsilon)))
+                    && answer.next().is_none()
+            }
+        }
+    }
+}
+
+fn float_eq(output: &str, answer: &str, epsilon: f64) -> bool {
+    if output == answer {
+        return true;
+    }
+    match (output.parse::<f64>(), answer.parse::<f64>()) {
+        (Ok(x), Ok(y)) if x.is_finite() && y.is_finite() => {
+            let error = (x - y).abs();
+            error <= epsilon || error <= epsilon * y.abs()
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact() {
+        assert!(Comparator::Exact.compare("1 2\n", "1 2\n", 0.0));
+        assert!(!Comparator::Exact.compare("1 2", "1 2\n", 0.0));
+    }
+
+    #[test]
+    fn lines() {
+        assert!(Comparator::Lines.compare("1 2  \r\n3\n\n", "1 2\n3", 0.0));
+        assert!(!Comparator::Lines.compare("1  2\n3", "1 2\n3", 0.0));
+        assert!(!Comparator::Lines.compare("1 2 3", "1 2\n3", 0.0));
+    }
+
+    #[test]
+    fn tokens() {
+        assert!(Comparator::Tokens.compare("1\n2  3\n", "1 2 3", 0.0));
+        assert!(!Comparator::Tokens.compare("1 2", "1 2 3", 0.0));
+        assert!(!Comparator::Tokens.compare("YES", "yes", 0.0));
+        assert!(Comparator::TokensIgnoreCase.compare("YES\nno", "yes No", 0.0));
+        assert!(!Comparator::TokensIgnoreCase.compare("yes", "yes no", 0.0));
+        assert!(!Comparator::TokensIgnoreCase.compare("yes no", "yes", 0.0));
+    }
+
+    #[test]
+    fn float() {
+        assert!(Comparator::Float.compare("0.3333", "0.33333333", 1e-4));
+        assert!(!Comparator::Float.compare("0.333", "0.33333333", 1e-4));
+        // Relative error for large numbers
+        assert!(Comparator::Float.compare("1000000.5", "1000000", 1e-6));
+        assert!(Comparator::Float.compare("Case 1: 2.0", "Case 1: 2", 1e-9));
+        assert!(!Comparator::Float.compare("nan", "nan0", 1e-9));
+        assert!(!Comparator::Float.compare("inf", "1e309", 1e-9));
+        assert!(!Comparator::Float.compare("1.0 2.0", "1.0", 1e-9));
+    }
+}
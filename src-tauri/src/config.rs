@@ -1,41 +1,128 @@
-use handlebars::Handlebars;
+use chrono::{DateTime, Local};
+use handlebars::{no_escape, Handlebars};
 use handlebars_misc_helpers::register;
 use regex::Regex;
-use serde::{Deserialize, Serialize};
+use schemars::{schema_for, JsonSchema};
+use serde::{ser::SerializeStruct, Deserialize, Serialize, Serializer};
 use serde_json::json;
 use std::collections::{BTreeMap, VecDeque, HashMap, HashSet};
+use std::fmt::Display;
 use std::fs;
-use std::io::Write;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use std::{path::Path};
-use tauri::State;
+use notify::{RecursiveMode, Watcher};
+use tauri::{Emitter, Manager, State};
+use toml_edit::{Item, TableLike};
 
-use crate::utils::{extract_code_block, ResultTrait};
-use crate::{utils::resolve_path, AppState, Problem};
+use crate::bundle::{get_rust_modules, inline_imports, inline_includes, Bundler, SourceMap};
+use crate::compare::Comparator;
+use crate::utils::{extract_code_block_with_line, ResultTrait};
+use crate::watch;
+use crate::{utils::resolve_path, AppState, Language, Problem};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Config {
+    /// Name used in file headers
     pub author: String,
     pub code: Code,
+    /// Library files and directories, by the name they are used with
     pub include: HashMap<String, String>,
+    /// Command that opens solution files
     pub editor: String,
     pub toggle: ToggleSettings,
+    #[serde(default)]
+    pub judge: JudgeSettings,
+    /// Overrides for single languages, keyed by their id or name in `Languages.toml`
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub profiles: HashMap<String, Profile>,
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+/// Fields of `[code]` and the `[include]` map that a language uses instead of the common ones.
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
+pub struct Profile {
+    pub filename: Option<String>,
+    pub template: Option<String>,
+    pub modifier: Option<String>,
+    pub lib_check_regex: Option<String>,
+    pub header: Option<String>,
+    pub bundler: Option<Bundler>,
+    pub line_directives: Option<bool>,
+    pub prune: Option<bool>,
+    pub include: Option<HashMap<String, String>>,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct ToggleSettings {
     // pub create_file: bool,
+    /// Run the tests whenever the solution file is saved
     pub run_on_save: bool,
+    /// Submit the solution once every test is accepted
     pub submit_on_ac: bool,
+    /// Open parsed problems right away instead of keeping them in the inbox
+    #[serde(default = "default_auto_activate")]
+    pub auto_activate: bool,
+}
+
+fn default_auto_activate() -> bool {
+    true
 }
 
-#[derive(Default, Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, JsonSchema, Clone)]
+#[serde(default)]
+pub struct JudgeSettings {
+    /// Path of the `testlib.h` copied next to testlib checkers
+    pub testlib: String,
+    /// Comparator used for problems that don't set their own
+    pub comparator: Comparator,
+    /// Allowed absolute or relative error of the `float` comparator
+    pub epsilon: f64,
+    /// Output limit of a test in megabytes, for stdout and stderr each
+    pub output_limit: usize,
+    /// Number of tests run at the same time, 0 for one per CPU and 1 to run them serially
+    pub parallelism: usize,
+}
+
+impl Default for JudgeSettings {
+    fn default() -> Self {
+        JudgeSettings {
+            testlib: "./testlib.h".into(),
+            comparator: Comparator::Lines,
+            epsilon: 1e-6,
+            output_limit: 64,
+            parallelism: 0,
+        }
+    }
+}
+
+#[derive(Default, Debug, Serialize, Deserialize, JsonSchema, Clone)]
 pub struct Code {
+    /// Handlebars template of the solution path, relative to the workspace
     pub filename: String,
+    /// Path of the file new solutions start from
     pub template: String,
+    /// Handlebars template assembling the code with the library files it uses
     pub modifier: String,
+    /// Handlebars template of the regex telling whether a library file is used
     pub lib_check_regex: String,
+    /// Handlebars template written above the template in new files, empty for no header
+    #[serde(default = "default_header")]
+    pub header: String,
+    /// How the library files used by the solution are found
+    #[serde(default)]
+    pub bundler: Bundler,
+    /// Mark where inlined headers start and end with `#line`, for the `cpp` bundler
+    #[serde(default)]
+    pub line_directives: bool,
+    /// Remove the functions, types and impls that `main` doesn't use, in Rust and C++
+    #[serde(default)]
+    pub prune: bool,
+}
+
+fn default_header() -> String {
+    "{{language.comment}} Created by {{author}} at {{date}} {{time}}\n{{language.comment}} {{url}}\n"
+        .into()
 }
 
 impl Default for Config {
@@ -61,8 +148,8 @@ impl Default for Config {
 {{!-- Base code block --}}
 {{{code}}}
 
-{{!-- Iterate over each library in lib_files --}}
-{{#each lib_files}}
+{{!-- Iterate over each library in lib_map --}}
+{{#each lib_map}}
 mod {{@key}} {
     {{{this}}}
 }
@@ -70,6 +157,10 @@ mod {{@key}} {
 "#
                 .into(),
                 lib_check_regex: "use.*{{name}}(::|;)".into(),
+                header: default_header(),
+                bundler: Bundler::Template,
+                line_directives: false,
+                prune: false,
             },
             include: HashMap::new(),
             editor: "code".into(),
@@ -77,37 +168,174 @@ mod {{@key}} {
                 // create_file: true,
                 run_on_save: true,
                 submit_on_ac: false,
+                auto_activate: true,
             },
+            judge: JudgeSettings::default(),
+            profiles: HashMap::new(),
         }
     }
 }
 
+/// Everything the filename and template Handlebars templates can use.
 #[derive(Serialize)]
+pub struct ProblemContext {
+    pub title: String,
+    /// Title without the problem index, e.g. "Watermelon" for "A. Watermelon"
+    pub name: String,
+    /// Index of the problem in its contest, e.g. "A", empty when the title has none
+    pub index: String,
+    pub url: String,
+    /// Group given by Competitive Companion, e.g. "Codeforces - Codeforces Round 1 (Div. 2)"
+    pub group: String,
+    /// Judge part of the group, e.g. "Codeforces"
+    pub judge: String,
+    /// Contest part of the group, e.g. "Codeforces Round 1 (Div. 2)"
+    pub contest: String,
+    /// In milliseconds
+    pub time_limit: usize,
+    /// In megabytes
+    pub memory_limit: usize,
+    pub interactive: bool,
+    pub language: LanguageContext,
+    pub author: String,
+    /// Formatted like "2024/12/25"
+    pub date: String,
+    /// Formatted like "14:07"
+    pub time: String,
+}
+
+#[derive(Serialize)]
+pub struct LanguageContext {
+    pub name: String,
+    /// Extension of the language's source file, e.g. "cpp"
+    pub extension: String,
+    /// Line comment, e.g. "//"
+    pub comment: String,
+}
+
+impl ProblemContext {
+    pub fn new(problem: &Problem, language: Option<&Language>, author: &str) -> Self {
+        let (index, name) = Regex::new(r"^([A-Za-z0-9]{1,4})(?:\.|:|\)| -)\s*(.+)$")
+            .unwrap()
+            .captures(&problem.title)
+            .map_or((String::new(), problem.title.clone()), |c| {
+                (c[1].to_string(), c[2].to_string())
+            });
+        let (judge, contest) = problem
+            .group
+            .split_once(" - ")
+            .unwrap_or((&problem.group, ""));
+        // Problems saved before the time was kept get the current one
+        let received = DateTime::from_timestamp(problem.received, 0)
+            .filter(|_| problem.received != 0)
+            .map_or_else(Local::now, |t| t.with_timezone(&Local));
+
+        ProblemContext {
+            title: problem.title.clone(),
+            name,
+            index,
+            url: problem.url.clone(),
+            group: problem.group.clone(),
+            judge: judge.into(),
+            contest: contest.into(),
+            time_limit: problem.time_limit,
+            memory_limit: problem.memory_limit,
+            interactive: problem.interactive,
+            language: LanguageContext {
+                name: language.map(|l| l.name.clone()).unwrap_or_default(),
+                extension: language
+                    .and_then(|l| Path::new(&l.source_file).extension())
+                    .map(|ext| ext.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                comment: language.map(|l| l.comment.clone()).unwrap_or_default(),
+            },
+            author: author.into(),
+            date: received.format("%Y/%m/%d").to_string(),
+            time: received.format("%H:%M").to_string(),
+        }
+    }
+}
+
 struct TemplateData {
     code: String,
-    lib_files: Vec<(String, String)>,
+    lib_files: Vec<(String, String, SourceMap)>,
+}
+
+/// Gives templates the library files both as `lib_files`, `[name, code]` pairs in the order in
+/// which they depend on each other, and as `lib_map`, a map from their names to their code.
+impl Serialize for TemplateData {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let files = || self.lib_files.iter().map(|(name, code, _)| (name, code));
+        let mut data = serializer.serialize_struct("TemplateData", 3)?;
+        data.serialize_field("code", &self.code)?;
+        data.serialize_field("lib_files", &files().collect::<Vec<_>>())?;
+        data.serialize_field("lib_map", &files().collect::<BTreeMap<_, _>>())?;
+        data.end()
+    }
 }
 
 impl Config {
-    pub fn get_filename(&self, problem: &Problem) -> Result<String, String> {
+    /// Key of the language's profile, looked up by id first and then by name.
+    pub fn get_profile_key(&self, language: &Language) -> Option<String> {
+        [language.id.to_string(), language.name.clone()]
+            .into_iter()
+            .find(|key| self.profiles.contains_key(key))
+    }
+
+    /// The config with the profile of the language applied, if it has one.
+    pub fn for_language(&self, language: &Language) -> Config {
+        let mut config = self.clone();
+        let Some(profile) = self
+            .get_profile_key(language)
+            .map(|key| &self.profiles[&key])
+        else {
+            return config;
+        };
+
+        let code = &mut config.code;
+        let fields = [
+            (&mut code.filename, &profile.filename),
+            (&mut code.template, &profile.template),
+            (&mut code.modifier, &profile.modifier),
+            (&mut code.lib_check_regex, &profile.lib_check_regex),
+            (&mut code.header, &profile.header),
+        ];
+        for (field, value) in fields {
+            if let Some(value) = value {
+                *field = value.clone();
+            }
+        }
+        if let Some(bundler) = profile.bundler {
+            code.bundler = bundler;
+        }
+        if let Some(line_directives) = profile.line_directives {
+            code.line_directives = line_directives;
+        }
+        if let Some(prune) = profile.prune {
+            code.prune = prune;
+        }
+        if let Some(include) = &profile.include {
+            config.include = include.clone();
+        }
+        config
+    }
+
+    pub fn get_filename(&self, context: &ProblemContext) -> Result<String, String> {
         let mut bars = Handlebars::new();
         register(&mut bars);
         bars.register_template_string("filename", &self.code.filename)
             .map_to_string()?;
-        let mut data = BTreeMap::new();
-        data.insert("title", problem.title.clone());
-        data.insert("url", problem.url.clone());
-        let name = bars.render("filename", &data).map_to_string()?;
+        let name = bars.render("filename", context).map_to_string()?;
         let name = name.trim().to_string();
         println!("name = {name}");
         Ok(name)
     }
 
-    pub fn get_file_path(&self, problem: &Problem, dir: &Path) -> Result<PathBuf, String> {
-        Ok(resolve_path(dir, &self.get_filename(problem)?))
+    pub fn get_file_path(&self, context: &ProblemContext, dir: &Path) -> Result<PathBuf, String> {
+        Ok(resolve_path(dir, &self.get_filename(context)?))
     }
 
-    fn get_included_files(&self, dir: &Path) -> Result<HashMap<String, String>, String> {
+    fn get_included_files(&self, dir: &Path) -> Result<HashMap<String, (PathBuf, String)>, String> {
         self.include
             .clone()
             .into_iter()
@@ -115,11 +343,9 @@ impl Config {
                 let path = resolve_path(dir, &value);
                 let mut files: HashMap<String, PathBuf> = HashMap::new();
                 if path.is_dir() {
-                    for f in path.read_dir().map_to_string()? {
-                        if let Ok(file) = f {
-                            if let Some(file_name) = file.path().file_stem() {
-                                files.insert(file_name.to_string_lossy().to_string(), file.path());
-                            }
+                    for file in path.read_dir().map_to_string()?.flatten() {
+                        if let Some(file_name) = file.path().file_stem() {
+                            files.insert(file_name.to_string_lossy().to_string(), file.path());
                         }
                     }
                 } else {
@@ -129,7 +355,7 @@ impl Config {
                     .into_iter()
                     .filter(|(_, v)| v.is_file())
                     .map(|(k, v)| match fs::read_to_string(&v) {
-                        Ok(content) => Ok((k, content)),
+                        Ok(content) => Ok((k, (v, content))),
                         Err(e) => Err(format!("Failed to read file {:?}: {}", v, e)),
                     })
                     .collect::<Result<Vec<_>, String>>()
@@ -138,18 +364,94 @@ impl Config {
             .map(|v| v.concat().into_iter().collect())
     }
 
-    pub fn get_template(&self, dir: &Path) -> String {
+    pub fn get_header(&self, context: &ProblemContext) -> Result<String, String> {
+        let mut bars = Handlebars::new();
+        register(&mut bars);
+        bars.register_escape_fn(no_escape);
+        bars.render_template(&self.code.header, context)
+            .map_to_string_mess("Error rendering header:")
+    }
+
+    /// Code new files start with. Templates ending in `.hbs` are rendered with Handlebars,
+    /// others are taken as they are, as code like `{{1, 2}}` in C++ isn't meant for it.
+    pub fn get_template(&self, context: &ProblemContext, dir: &Path) -> Result<String, String> {
         let template_path = resolve_path(dir, &self.code.template);
-        fs::read_to_string(template_path).unwrap_or_else(|e| {
+        let template = fs::read_to_string(&template_path).unwrap_or_else(|e| {
             eprintln!("Error reading template file: {}", e);
             String::new()
-        })
+        });
+        if template_path.extension().is_none_or(|ext| ext != "hbs") {
+            return Ok(template);
+        }
+
+        let mut bars = Handlebars::new();
+        register(&mut bars);
+        bars.register_escape_fn(no_escape);
+        bars.render_template(&template, context)
+            .map_to_string_mess("Error rendering template file:")
     }
 
-    pub fn get_final_code(&self, problem: &Problem, dir: &Path) -> Result<String, String> {
+    /// The code of the solution with the library files it uses, as `modifier` puts them
+    /// together, and where its lines come from.
+    pub fn get_final_code(
+        &self,
+        context: &ProblemContext,
+        dir: &Path,
+    ) -> Result<(String, SourceMap), String> {
         // Read source code
-        let source_code = fs::read_to_string(self.get_file_path(problem, dir)?).map_to_string()?;
+        let file_path = self.get_file_path(context, dir)?;
+        let source_code = fs::read_to_string(&file_path).map_to_string()?;
+
+        let ((source_code, code_map), lib_files) = match self.code.bundler {
+            Bundler::Template => (
+                extract_code(&file_path, &source_code),
+                self.get_lib_files(&source_code, dir)?,
+            ),
+            Bundler::Rust => (
+                extract_code(&file_path, &source_code),
+                get_rust_modules(&file_path, &source_code, &self.include, dir)?,
+            ),
+            Bundler::Cpp => {
+                let code = inline_includes(
+                    &file_path,
+                    &source_code,
+                    &self.include,
+                    dir,
+                    self.code.line_directives,
+                )?;
+                (code, vec![])
+            }
+            Bundler::Python => {
+                let code = inline_imports(&file_path, &source_code, &self.include, dir)?;
+                (code, vec![])
+            }
+        };
 
+        let mut bars = Handlebars::new();
+        register(&mut bars);
+        bars.register_template_string("modify", &self.code.modifier)
+            .map_to_string()?;
+
+        // Prepare context for the template
+        let data = TemplateData {
+            code: source_code,
+            lib_files,
+        };
+
+        let res = bars.render("modify", &data).map_to_string()?;
+
+        #[cfg(debug_assertions)]
+        print!("{res}");
+
+        let pieces: Vec<_> = std::iter::once((data.code.as_str(), &code_map))
+            .chain(data.lib_files.iter().map(|(_, code, map)| (code.as_str(), map)))
+            .collect();
+        let map = SourceMap::assemble(&res, &pieces);
+        Ok((res, map))
+    }
+
+    /// Library files used by the code according to `lib_check_regex`, each after the ones it uses.
+    fn get_lib_files(&self, source_code: &str, dir: &Path) -> Result<Vec<(String, String, SourceMap)>, String> {
         // Get included files content
         let included_files = self.get_included_files(dir)?;
 
@@ -163,14 +465,14 @@ impl Config {
             .map_to_string()?;
 
         // Determine initial dependencies from source_code
-        for (k, _) in &included_files {
+        for k in included_files.keys() {
             let re = Regex::new(
                 &bars
                     .render("libcheck", &json!({"name": k}))
                     .map_to_string()?,
             )
                 .map_to_string_mess("Invalid regex for lib_check")?;
-            if re.is_match(&source_code) {
+            if re.is_match(source_code) {
                 deque.push_back(k.clone());
             }
         }
@@ -183,11 +485,11 @@ impl Config {
                 continue;
             }
 
-            let v = included_files.get(&d).unwrap(); // 👈 just borrow, don't remove yet
+            let (path, v) = included_files.get(&d).unwrap(); // 👈 just borrow, don't remove yet
             let mut deps = HashSet::new();
 
             // Search for nested dependencies
-            for (k, _) in &included_files {
+            for k in included_files.keys() {
                 if k == &d {
                     continue; // avoid self-dep or reprocessing
                 }
@@ -208,7 +510,7 @@ impl Config {
             }
 
             graph.insert(d.clone(), deps);
-            visited.insert(d.clone(), extract_code_block(v));
+            visited.insert(d.clone(), extract_code(path, v));
         }
 
         #[cfg(debug_assertions)]
@@ -224,29 +526,19 @@ impl Config {
         let lib_files = sorted_libs
             .into_iter()
             .rev()
-            .filter_map(|k| visited.get(&k).map(|v| (k, v.clone())))
+            .filter_map(|k| visited.get(&k).map(|(v, map)| (k, v.clone(), map.clone())))
             .collect::<Vec<_>>(); // or regular BTreeMap/HashMap if order not needed beyond template
 
-        let source_code = extract_code_block(&source_code);
-
-        bars.register_template_string("modify", &self.code.modifier)
-            .map_to_string()?;
-
-        // Prepare context for the template
-        let data = TemplateData {
-            code: source_code,
-            lib_files,
-        };
-
-        let res = bars.render("modify", &data).map_to_string()?;
-
-        #[cfg(debug_assertions)]
-        print!("{res}");
-
-        Ok(res)
+        Ok(lib_files)
     }
 }
 
+/// The code block of a file, with its lines mapped to the file.
+fn extract_code(path: &Path, source: &str) -> (String, SourceMap) {
+    let (code, line) = extract_code_block_with_line(source);
+    (code, SourceMap::new(path, line))
+}
+
 fn topo_sort(graph: &HashMap<String, HashSet<String>>) -> Result<Vec<String>, String> {
     let mut in_degree = HashMap::new();
     let mut order = Vec::new();
@@ -289,31 +581,427 @@ fn topo_sort(graph: &HashMap<String, HashSet<String>>) -> Result<Vec<String>, St
     }
 }
 
+/// Where and why a config file couldn't be used.
+#[derive(Serialize, Clone, Debug)]
+pub struct ConfigError {
+    pub file: String,
+    /// Starting from 1, when the error points at a place in the file
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+impl ConfigError {
+    pub fn new(file: &Path, message: impl Display) -> Self {
+        ConfigError {
+            file: file.to_string_lossy().into(),
+            line: None,
+            column: None,
+            message: message.to_string(),
+        }
+    }
+
+    pub fn parse(file: &Path, content: &str, error: &toml::de::Error) -> Self {
+        let (line, column) = error
+            .span()
+            .map(|span| {
+                let before = &content[..span.start];
+                let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+                (
+                    before.matches('\n').count() + 1,
+                    before[line_start..].chars().count() + 1,
+                )
+            })
+            .unzip();
+        ConfigError {
+            line,
+            column,
+            ..ConfigError::new(file, error.message())
+        }
+    }
+}
+
+impl Display for ConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match (self.line, self.column) {
+            (Some(line), Some(column)) => {
+                write!(f, "{}:{}:{}: {}", self.file, line, column, self.message)
+            }
+            _ => write!(f, "{}: {}", self.file, self.message),
+        }
+    }
+}
+
+/// Config files merged over the default config, remembering which file set each value.
+#[derive(Debug, Clone)]
+pub struct LayeredConfig {
+    table: toml::Table,
+    /// Dotted key of every value to the file it came from
+    sources: BTreeMap<String, String>,
+}
+
+impl Default for LayeredConfig {
+    fn default() -> Self {
+        let table = toml::Table::try_from(Config::default()).expect("default config is valid toml");
+        let mut sources = BTreeMap::new();
+        for (key, value) in &table {
+            record_sources(key, value, "default", &mut sources);
+        }
+        LayeredConfig { table, sources }
+    }
+}
+
+impl LayeredConfig {
+    /// Merges the file over the layers so far, table by table. Missing files are skipped, and
+    /// a file that doesn't parse or leaves the config invalid isn't merged.
+    pub fn merge_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+        if !path.is_file() {
+            return Ok(());
+        }
+        let content = fs::read_to_string(path).map_err(|e| ConfigError::new(path, e))?;
+        self.merge_str(path, &content)
+    }
+
+    /// Merges the content as if it was read from the file at the path.
+    pub fn merge_str(&mut self, path: &Path, content: &str) -> Result<(), ConfigError> {
+        let layer: toml::Table =
+            toml::from_str(content).map_err(|e| ConfigError::parse(path, content, &e))?;
+
+        let previous = self.clone();
+        let source = path.to_string_lossy();
+        merge_tables(&mut self.table, layer, "", &source, &mut self.sources);
+        if let Err(e) = self.get_config() {
+            *self = previous;
+            return Err(ConfigError::new(path, e));
+        }
+        Ok(())
+    }
+
+    pub fn get_sources(&self) -> &BTreeMap<String, String> {
+        &self.sources
+    }
+
+    pub fn get_config(&self) -> Result<Config, String> {
+        self.table
+            .clone()
+            .try_into()
+            .map_to_string_mess("Invalid config:")
+    }
+}
+
+fn merge_tables(
+    base: &mut toml::Table,
+    layer: toml::Table,
+    prefix: &str,
+    source: &str,
+    sources: &mut BTreeMap<String, String>,
+) {
+    for (key, value) in layer {
+        let path = format!("{prefix}{key}");
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(layer)) => {
+                sources.remove(&path);
+                merge_tables(base, layer, &format!("{path}."), source, sources);
+            }
+            (_, value) => {
+                // Whatever was under the key is replaced as a whole
+                sources.retain(|k, _| !k.starts_with(&format!("{path}.")));
+                record_sources(&path, &value, source, sources);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record_sources(
+    path: &str,
+    value: &toml::Value,
+    source: &str,
+    sources: &mut BTreeMap<String, String>,
+) {
+    match value {
+        toml::Value::Table(table) if !table.is_empty() => {
+            for (key, value) in table {
+                record_sources(&format!("{path}.{key}"), value, source, sources);
+            }
+        }
+        _ => {
+            sources.insert(path.into(), source.into());
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct EffectiveConfig {
+    config: Config,
+    /// Dotted key of every value to the file it came from, or "default"
+    sources: BTreeMap<String, String>,
+}
+
+/// The config used for the current problem and language, along with where each value is from.
+#[tauri::command]
+pub fn get_effective_config(state: State<'_, Mutex<AppState>>) -> Result<EffectiveConfig, String> {
+    let state = state.lock().unwrap();
+    let layers = state.get_layers(&state.problem).map_to_string()?;
+    let mut config = layers.get_config()?;
+    let mut sources = layers.sources.clone();
+
+    if let Ok(language) = state.get_language() {
+        if let Some(key) = config.get_profile_key(&language) {
+            let prefix = format!("profiles.{key}.");
+            if config.profiles[&key].include.is_some() {
+                sources.retain(|k, _| !k.starts_with("include."));
+            }
+            for (path, source) in &layers.sources {
+                let Some(field) = path.strip_prefix(&prefix) else {
+                    continue;
+                };
+                let target = if field.starts_with("include") {
+                    field.to_string()
+                } else {
+                    format!("code.{field}")
+                };
+                sources.insert(target, source.clone());
+            }
+        }
+        config = config.for_language(&language);
+    }
+
+    Ok(EffectiveConfig { config, sources })
+}
+
+/// Paths of the global and the workspace config.
+pub fn get_config_paths(state: &AppState, handle: &tauri::AppHandle) -> Vec<PathBuf> {
+    let mut paths = vec![];
+    if let Ok(global_dir) = handle.path().app_config_dir() {
+        paths.push(global_dir.join("config.toml"));
+    }
+    paths.push(state.directory.join("config.toml"));
+    paths
+}
+
+/// Reads the global config and then the one of the workspace into the state.
+pub fn load_config(state: &mut AppState, handle: &tauri::AppHandle) -> Result<(), ConfigError> {
+    let mut layers = LayeredConfig::default();
+    for path in get_config_paths(state, handle) {
+        layers.merge_file(&path)?;
+    }
+
+    state.config = layers
+        .get_config()
+        .map_err(|e| ConfigError::new(Path::new("config.toml"), e))?;
+    state.config_layers = layers;
+    Ok(())
+}
+
 #[tauri::command]
-pub fn read_config(state: State<'_, Mutex<AppState>>) -> Result<(), String> {
+pub fn read_config(
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
     let mut state = state.lock().unwrap();
-    let mut path = state.directory.clone();
-    path.push("config.toml");
+    load_config(&mut state, &handle).map_to_string()?;
+
+    // Pick up later changes of the config files
+    watch::set_config(&get_config_paths(&state, &handle));
+    let global_dir = handle.path().app_config_dir().map_to_string()?;
+    if let Some(watcher) = &state.watcher {
+        let mut watcher = watcher.write().unwrap();
+        for dir in [&global_dir, &state.directory] {
+            watcher
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_to_string()?;
+        }
+    }
 
-    let config: Config = if path.exists() {
-        let content =
-            fs::read_to_string(&path).map_err(|e| format!("Error reading {:?}: {}", path, e))?;
-        toml::from_str(&content).map_err(|e| format!("Error parsing config.toml: {}", e))?
-    } else {
-        // File doesn't exist: create with default content
-        let default_config = Config::default();
-        let toml_str = toml::to_string_pretty(&default_config)
-            .map_err(|e| format!("Error serializing default config: {}", e))?;
+    // The tests of the last problem can only be found once the filename template is known
+    if state.load_tests().unwrap_or(false) {
+        handle
+            .emit("set-verdicts", &state.verdicts)
+            .map_to_string()?;
+    }
+
+    Ok(())
+}
 
-        let mut file =
-            fs::File::create(&path).map_err(|e| format!("Error creating config.toml: {}", e))?;
-        file.write_all(toml_str.as_bytes())
-            .map_err(|e| format!("Error writing config.toml: {}", e))?;
+/// JSON schema of `config.toml`, from which the settings can be laid out.
+#[tauri::command]
+pub fn get_config_schema() -> serde_json::Value {
+    serde_json::to_value(schema_for!(Config)).expect("schema is valid json")
+}
 
-        default_config
+/// Which config file an edit is written to.
+#[derive(Deserialize, Clone, Copy, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigScope {
+    Global,
+    #[default]
+    Workspace,
+}
+
+/// Sets a single value in the global or the workspace config, e.g. `toggle.run_on_save` or
+/// `include.dsu`, keeping the comments and the order of the file. A null value removes the key.
+#[tauri::command]
+pub fn set_config_value(
+    key: String,
+    value: serde_json::Value,
+    scope: Option<ConfigScope>,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<Config, String> {
+    let mut state = state.lock().unwrap();
+    let keys = toml_edit::Key::parse(&key).map_to_string()?;
+    if !schema_has_key(&get_config_schema(), &keys) {
+        return Err(format!("Unknown config key: {key}"));
+    }
+
+    let path = match scope.unwrap_or_default() {
+        ConfigScope::Global => handle.path().app_config_dir().map_to_string()?,
+        ConfigScope::Workspace => state.directory.clone(),
+    }
+    .join("config.toml");
+    let content = if path.is_file() {
+        fs::read_to_string(&path).map_to_string()?
+    } else {
+        String::new()
     };
+    let mut document: toml_edit::DocumentMut = content.parse().map_to_string()?;
+    set_value(document.as_table_mut(), &keys, value)?;
+    let content = document.to_string();
+
+    // Nothing is written that would stop the config from loading
+    let mut layers = LayeredConfig::default();
+    for other in get_config_paths(&state, &handle) {
+        if other == path {
+            layers.merge_str(&path, &content)
+        } else {
+            layers.merge_file(&other)
+        }
+        .map_to_string()?;
+    }
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_to_string()?;
+    }
+    fs::write(&path, content).map_to_string()?;
+    load_config(&mut state, &handle).map_to_string()?;
+    Ok(state.config.clone())
+}
 
-    state.config = config;
+/// Sets the value under the keys, adding the tables on the way. What surrounds a replaced
+/// value, like a comment after it, is kept.
+fn set_value(
+    mut table: &mut dyn TableLike,
+    keys: &[toml_edit::Key],
+    value: serde_json::Value,
+) -> Result<(), String> {
+    let (last, parents) = keys.split_last().ok_or("Empty config key")?;
+    for key in parents {
+        if value.is_null() && !table.contains_key(key.get()) {
+            return Ok(());
+        }
+        table = table
+            .entry(key.get())
+            .or_insert_with(|| {
+                let mut table = toml_edit::Table::new();
+                table.set_implicit(true);
+                Item::Table(table)
+            })
+            .as_table_like_mut()
+            .ok_or_else(|| format!("{} is not a table", key.get()))?;
+    }
 
+    if value.is_null() {
+        table.remove(last.get());
+        return Ok(());
+    }
+    let value = toml_value(value)?;
+    match (table.get_mut(last.get()), value) {
+        (Some(Item::Value(previous)), value) => {
+            let decor = previous.decor().clone();
+            *previous = value;
+            *previous.decor_mut() = decor;
+        }
+        (Some(Item::Table(previous)), toml_edit::Value::InlineTable(value)) => {
+            let decor = previous.decor().clone();
+            *previous = value.into_table();
+            *previous.decor_mut() = decor;
+        }
+        (None, toml_edit::Value::InlineTable(value)) => {
+            table.insert(last.get(), Item::Table(value.into_table()));
+        }
+        (_, value) => {
+            table.insert(last.get(), Item::Value(value));
+        }
+    }
     Ok(())
 }
+
+/// The JSON value as a TOML one, with objects as inline tables. Nulls in objects are left
+/// out since TOML has no null.
+fn toml_value(value: serde_json::Value) -> Result<toml_edit::Value, String> {
+    use serde_json::Value as Json;
+    Ok(match value {
+        Json::Null => return Err("null can't be written to the config".into()),
+        Json::Bool(b) => b.into(),
+        Json::Number(n) => match n.as_i64() {
+            Some(i) => i.into(),
+            None => n.as_f64().ok_or(format!("{n} is out of range"))?.into(),
+        },
+        Json::String(s) => s.into(),
+        Json::Array(values) => {
+            let mut array = toml_edit::Array::new();
+            for value in values {
+                array.push(toml_value(value)?);
+            }
+            toml_edit::Value::Array(array)
+        }
+        Json::Object(entries) => {
+            let mut table = toml_edit::InlineTable::new();
+            for (key, value) in entries {
+                if !value.is_null() {
+                    table.insert(&key, toml_value(value)?);
+                }
+            }
+            toml_edit::Value::InlineTable(table)
+        }
+    })
+}
+
+/// Whether the keys lead to a value in the schema of the config, so typos aren't written.
+fn schema_has_key(schema: &serde_json::Value, keys: &[toml_edit::Key]) -> bool {
+    let mut node = schema;
+    for key in keys {
+        node = resolve_schema(schema, node);
+        let next = node["properties"]
+            .get(key.get())
+            .or_else(|| node.get("additionalProperties").filter(|x| x.is_object()));
+        match next {
+            Some(next) => node = next,
+            None => return false,
+        }
+    }
+    true
+}
+
+/// Follows references and the wrappers around optional values to the schema of the value.
+fn resolve_schema<'a>(
+    root: &'a serde_json::Value,
+    mut node: &'a serde_json::Value,
+) -> &'a serde_json::Value {
+    loop {
+        if let Some(name) = node["$ref"]
+            .as_str()
+            .and_then(|x| x.strip_prefix("#/definitions/"))
+        {
+            node = &root["definitions"][name];
+        } else if let Some(inner) = ["allOf", "anyOf"]
+            .iter()
+            .find_map(|k| node[k].as_array()?.iter().find(|x| x["type"] != "null"))
+        {
+            node = inner;
+        } else {
+            return node;
+        }
+    }
+}
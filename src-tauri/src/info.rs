@@ -1,8 +1,11 @@
-use crate::judge::Verdict;
-use crate::WINDOW;
+use crate::compare::Comparator;
+use crate::judge::{Checker, Program, TestSource, Verdict};
+use crate::{AppState, WINDOW};
 use actix_web::{post, web, HttpResponse, Responder};
+use chrono::Local;
 use serde::{Deserialize, Serialize};
-use tauri::Emitter;
+use std::sync::Mutex;
+use tauri::{Emitter, Manager};
 
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -14,6 +17,69 @@ pub struct Info {
     memory_limit: usize, // mb
     time_limit: usize,   // ms
     tests: Vec<Test>,
+    #[serde(default)]
+    test_type: TestType,
+    #[serde(default)]
+    input: Input,
+    #[serde(default)]
+    output: Output,
+    #[serde(default)]
+    languages: Languages,
+    #[serde(default)]
+    batch: Batch,
+}
+
+/// Problems parsed together, e.g. a whole contest, share the batch id.
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Batch {
+    pub id: String,
+    pub size: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub enum TestType {
+    #[default]
+    Single,
+    /// The first line of the input is the number of test cases
+    MultiNumber,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Input {
+    #[default]
+    Stdin,
+    File {
+        #[serde(rename = "fileName")]
+        file_name: String,
+    },
+    /// The input file is the one matching the pattern
+    Regex { pattern: String },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Output {
+    #[default]
+    Stdout,
+    File {
+        #[serde(rename = "fileName")]
+        file_name: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct Languages {
+    #[serde(default)]
+    pub java: Option<JavaSettings>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct JavaSettings {
+    pub main_class: String,
+    pub task_class: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -28,19 +94,48 @@ pub struct Problem {
     pub url: String,
     pub memory_limit: usize,
     pub time_limit: usize,
+    #[serde(default)]
+    pub interactive: bool,
+    #[serde(default)]
+    pub interactor: Option<Program>,
+    #[serde(default)]
+    pub checker: Option<Checker>,
+    #[serde(default)]
+    pub comparator: Option<Comparator>,
+    #[serde(default)]
+    pub epsilon: Option<f64>,
+    #[serde(default)]
+    pub generator: Option<Program>,
+    #[serde(default)]
+    pub brute: Option<Program>,
+    #[serde(default)]
+    pub group: String,
+    #[serde(default)]
+    pub test_type: TestType,
+    #[serde(default)]
+    pub input: Input,
+    #[serde(default)]
+    pub output: Output,
+    #[serde(default)]
+    pub languages: Languages,
+    /// When the problem was received, as a unix timestamp, for the date and time of templates
+    #[serde(default)]
+    pub received: i64,
+}
+
+/// The problems of a batch, in the order they were received.
+#[derive(Deserialize, Serialize, Clone, Default)]
+pub struct Contest {
+    pub id: String,
+    pub size: usize,
+    pub problems: Vec<Problem>,
 }
 
 impl Test {
     pub fn get_verdict(&self) -> Verdict {
         Verdict {
-            answer: self.output.clone(),
-            input: self.input.clone(),
-            output: "".into(),
-            stderr: "".into(),
-            memory: 0.0,
-            time: 0.0,
-            status_id: 0,
-            status: "NA".into(),
+            source: TestSource::Sample,
+            ..Verdict::new(self.input.clone(), self.output.clone())
         }
     }
 }
@@ -52,6 +147,14 @@ impl Info {
             memory_limit: self.memory_limit,
             time_limit: self.time_limit,
             url: self.url.clone(),
+            interactive: self.interactive,
+            group: self.group.clone(),
+            test_type: self.test_type,
+            input: self.input.clone(),
+            output: self.output.clone(),
+            languages: self.languages.clone(),
+            received: Local::now().timestamp(),
+            ..Default::default()
         }
     }
 
@@ -62,10 +165,57 @@ impl Info {
 
 #[post("/")]
 pub async fn get_info(req_body: web::Json<Info>) -> impl Responder {
-    let window = WINDOW.get().expect("window-is-unavailable");
-    window.emit("set-problem", req_body.get_problem()).unwrap();
-    window
-        .emit("set-verdicts", req_body.get_verdicts())
-        .unwrap();
+    let Some(window) = WINDOW.get() else {
+        return HttpResponse::ServiceUnavailable();
+    };
+    let state = window.state::<Mutex<AppState>>();
+    let mut state = state.lock().unwrap();
+    let problem = req_body.get_problem();
+
+    // Only the first problem of a batch is opened, the rest are kept for later
+    let first = if req_body.batch.size > 1 {
+        let batch = &req_body.batch;
+        let contest = state.contest.get_or_insert_with(Contest::default);
+        if contest.id != batch.id {
+            *contest = Contest {
+                id: batch.id.clone(),
+                size: batch.size,
+                problems: vec![],
+            };
+        }
+        if !contest.problems.iter().any(|p| p.url == problem.url) {
+            contest.problems.push(problem.clone());
+        }
+        contest.problems[0].url == problem.url
+    } else {
+        true
+    };
+
+    if first && state.config.toggle.auto_activate {
+        if let Err(e) = state.open_problem(problem, req_body.get_verdicts()) {
+            let _ = window.emit("error", format!("Failed to save the tests: {e}"));
+        }
+        let _ = window.emit("set-problem", &state.problem);
+        let _ = window.emit("set-verdicts", &state.verdicts);
+    } else if first {
+        // Don't replace what the user is working on
+        state.receive_problem(problem, req_body.get_verdicts());
+        let _ = window.emit("set-inbox", &state.inbox);
+    } else {
+        let saved = match state.read_tests(&problem) {
+            Ok(Some(_)) => Ok(()),
+            _ => state.save_tests_of(&problem, &req_body.get_verdicts()),
+        };
+        if let Err(e) = saved.and_then(|_| state.write_solution_file(&problem)) {
+            let _ = window.emit("error", format!("Failed to prepare {}: {e}", problem.title));
+        }
+    }
+
+    if req_body.batch.size > 1 {
+        let _ = window.emit("set-contest", &state.contest);
+    }
+    if let Err(e) = state.save(window.app_handle()) {
+        let _ = window.emit("error", format!("Failed to save the state: {e}"));
+    }
     HttpResponse::Ok()
 }
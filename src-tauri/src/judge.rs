@@ -1,11 +1,23 @@
-use crate::{state::AppState, utils::*, Language, WINDOW};
+use crate::{
+    bundle::SourceMap,
+    compare::Comparator,
+    process::{self, Exit, Limits, Usage},
+    state::AppState,
+    utils::*,
+    Language, WINDOW,
+};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 use std::{
     fs::{self, create_dir_all, remove_dir_all},
-    io::{Read, Write},
-    path::Path,
-    process::{Command, Stdio},
-    sync::Mutex,
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Child, Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Mutex, OnceLock,
+    },
+    thread,
     time::{Duration, Instant},
 };
 use tauri::{Emitter, State};
@@ -19,6 +31,9 @@ use std::os::windows::process::CommandExt;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000; // Prevents opening a new window
 
+/// Least time a checker, generator or brute force solution gets before it is considered stuck
+const HELPER_TIME_LIMIT: Duration = Duration::from_secs(10);
+
 #[derive(Serialize, Deserialize, Clone, Default)]
 pub struct Verdict {
     pub input: String,
@@ -29,6 +44,254 @@ pub struct Verdict {
     pub status: String,
     pub time: f32,
     pub memory: f32,
+    #[serde(default)]
+    pub message: String,
+    /// Stays the same while the test is edited or moved around
+    #[serde(default)]
+    pub id: String,
+    #[serde(default)]
+    pub source: TestSource,
+}
+
+/// Where a test came from.
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TestSource {
+    /// Parsed along with the problem
+    Sample,
+    /// Added by hand
+    #[default]
+    Custom,
+    /// Found by stress testing or minimizing another test
+    Generated,
+}
+
+impl Verdict {
+    pub fn new(input: String, answer: String) -> Self {
+        Verdict {
+            input,
+            answer,
+            status: "NA".into(),
+            id: Uuid::new_v4().to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Forgets the result of the last run, keeping the test itself.
+    pub fn reset(&mut self) {
+        *self = Verdict {
+            id: std::mem::take(&mut self.id),
+            source: self.source,
+            ..Verdict::new(
+                std::mem::take(&mut self.input),
+                std::mem::take(&mut self.answer),
+            )
+        };
+    }
+}
+
+/// Sent to the frontend as soon as a single test is done.
+#[derive(Serialize, Clone)]
+pub struct TestResult<'a> {
+    pub index: usize,
+    pub id: &'a str,
+    pub verdict: &'a Verdict,
+}
+
+/// Sent to the frontend once all the tests are done.
+#[derive(Serialize, Clone)]
+pub struct Summary {
+    pub passed: usize,
+    pub total: usize,
+    pub max_time: f32,
+    pub max_memory: f32,
+}
+
+impl Summary {
+    pub fn new(verdicts: &[Verdict]) -> Self {
+        Summary {
+            passed: verdicts.iter().filter(|v| v.status_id == 3).count(),
+            total: verdicts.len(),
+            max_time: verdicts.iter().map(|v| v.time).fold(0.0, f32::max),
+            max_memory: verdicts.iter().map(|v| v.memory).fold(0.0, f32::max),
+        }
+    }
+}
+
+/// A helper program (e.g. an interactor) written in any language from `Languages.toml`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Program {
+    pub file: String,
+    pub language_id: usize,
+}
+
+impl Program {
+    /// Copies the program into its own temporary directory without compiling it.
+    pub fn prepare(&self, state: &AppState, name: &str) -> Result<Executable, String> {
+        let language = state
+            .languages
+            .get(&self.language_id.to_string())
+            .ok_or(format!("language of the {name} not found in languages"))?
+            .clone();
+        let source = fs::read_to_string(state.directory.join(&self.file))
+            .map_to_string_mess(&format!("Failed to read the {name}"))?;
+
+        Executable::new(language, &source)
+    }
+
+    /// Copies the program into its own temporary directory and compiles it there.
+    pub fn build(&self, state: &AppState, name: &str) -> Result<Executable, String> {
+        let executable = self.prepare(state, name)?;
+        executable
+            .compile()
+            .map_err(|e| format!("Failed to compile the {name}\n{e}"))?;
+        Ok(executable)
+    }
+}
+
+/// A testlib-style checker, called as `checker input output answer`.
+#[derive(Serialize, Deserialize, Clone, Default)]
+pub struct Checker {
+    pub file: String,
+    pub language_id: usize,
+    /// Compile the checker as C++ next to the `testlib.h` from the config
+    #[serde(default)]
+    pub testlib: bool,
+}
+
+impl Checker {
+    pub fn build(&self, state: &AppState) -> Result<Executable, String> {
+        let program = Program {
+            file: self.file.clone(),
+            language_id: self.language_id,
+        };
+        let executable = program.prepare(state, "checker")?;
+
+        if self.testlib {
+            if !executable.language.source_file.ends_with(".cpp") {
+                return Err("testlib checkers must be written in C++".into());
+            }
+            let testlib = resolve_path(&state.directory, &state.get_config().judge.testlib);
+            fs::copy(testlib, executable.dir.join("testlib.h"))
+                .map_to_string_mess("Failed to copy testlib.h")?;
+        }
+
+        executable
+            .compile()
+            .map_err(|e| format!("Failed to compile the checker\n{e}"))?;
+        Ok(executable)
+    }
+}
+
+/// Source code placed in a fresh temporary directory, which is removed on drop.
+pub struct Executable {
+    pub language: Language,
+    pub dir: PathBuf,
+    /// Result of the compilation, which is only done once
+    compiled: OnceLock<Result<bool, String>>,
+}
+
+impl Executable {
+    pub fn new(language: Language, source: &str) -> Result<Self, String> {
+        // creating a temporary directory
+        let mut dir = std::env::temp_dir();
+        dir.push(Uuid::new_v4().to_string());
+        create_dir_all(&dir).map_to_string()?;
+
+        fs::write(dir.join(&language.source_file), source).map_to_string()?;
+
+        Ok(Executable {
+            language,
+            dir,
+            compiled: OnceLock::new(),
+        })
+    }
+
+    pub fn compile(&self) -> Result<bool, String> {
+        self.compiled
+            .get_or_init(|| compile(&self.language, &self.dir))
+            .clone()
+    }
+
+    /// Command running the compiled program, with the platform-specific options set.
+    fn command(&self) -> Command {
+        #[cfg(windows)]
+        let run_cmd = if self.language.run_cmd_win.is_empty() {
+            &self.language.run_cmd
+        } else {
+            &self.language.run_cmd_win
+        };
+
+        #[cfg(not(windows))]
+        let run_cmd = &self.language.run_cmd;
+
+        #[cfg(debug_assertions)]
+        println!("dir: {}, run_cmd: {}", self.dir.to_str().unwrap(), run_cmd);
+
+        let mut cmd = Command::new(resolve_path(&self.dir, run_cmd));
+        cmd.current_dir(&self.dir).args(&self.language.run_args);
+
+        #[cfg(windows)]
+        cmd.creation_flags(CREATE_NO_WINDOW);
+
+        cmd
+    }
+}
+
+impl Drop for Executable {
+    fn drop(&mut self) {
+        let _ = remove_dir_all(&self.dir);
+    }
+}
+
+pub struct Judge {
+    pub solution: Executable,
+    /// Where the lines of the solution's code come from
+    pub source_map: SourceMap,
+    /// Places in the solution's source file in diagnostics
+    places: Regex,
+    pub interactor: Option<Executable>,
+    pub checker: Option<Executable>,
+    pub comparator: Comparator,
+    pub epsilon: f64,
+    pub time_limit: usize,
+    pub memory_limit: usize,
+    pub output_limit: usize,
+    pub parallelism: usize,
+}
+
+#[tauri::command]
+pub fn set_interactor(
+    interactor: Option<Program>,
+    state: State<'_, Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.problem.interactive = interactor.is_some();
+    state.problem.interactor = interactor;
+    state.save_tests()
+}
+
+#[tauri::command]
+pub fn set_checker(
+    checker: Option<Checker>,
+    state: State<'_, Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.problem.checker = checker;
+    state.save_tests()
+}
+
+/// Overrides the comparator and epsilon from `config.toml` for the current problem.
+#[tauri::command]
+pub fn set_comparator(
+    comparator: Option<Comparator>,
+    epsilon: Option<f64>,
+    state: State<'_, Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.problem.comparator = comparator;
+    state.problem.epsilon = epsilon;
+    state.save_tests()
 }
 
 #[tauri::command]
@@ -38,23 +301,6 @@ pub async fn test(
 ) -> Result<(), String> {
     let state = app_state.lock().unwrap();
 
-    // creating a temporary directory
-    let mut dir = std::env::temp_dir();
-    dir.push(Uuid::new_v4().to_string());
-
-    let language = state.get_language()?;
-
-    let mut file_path = dir.clone();
-    file_path.push(&language.source_file);
-
-    let source_file = state
-        .config
-        .get_final_code(&state.problem, &state.directory)?;
-
-    // copy the final code into the temporary directory
-    create_dir_all(&dir).map_to_string()?;
-    fs::write(file_path, source_file).map_to_string()?;
-
     let mut verdicts = state.verdicts.clone();
     for v in &mut verdicts {
         v.status = "Compiling".into();
@@ -62,8 +308,12 @@ pub async fn test(
     }
     handle.emit("set-verdicts", &verdicts).map_to_string()?;
 
+    let (solution, source_map) = state.build_solution()?;
+    let judge = Judge::new(&state, solution, source_map)?;
+
     // First try to compiler and if compilation error occurs then return
-    if let Err(e) = compile(&language, &dir) {
+    if let Err(e) = judge.solution.compile() {
+        let e = judge.map_diagnostics(&e);
         for v in &mut verdicts {
             v.stderr = e.clone();
             v.status = "Compilation Error".into();
@@ -77,9 +327,21 @@ pub async fn test(
         }
         handle.emit("set-verdicts", &verdicts).map_to_string()?;
 
-        let time_limit = state.problem.time_limit;
-        let verdicts = run_all(&language, &dir, verdicts, time_limit)?;
-        if verdicts.iter().all(|v| v.status == "Accepted") && state.config.toggle.submit_on_ac {
+        let verdicts = judge.run_all(verdicts, |index, verdict| {
+            let _ = handle.emit(
+                "set-verdict",
+                TestResult {
+                    index,
+                    id: &verdict.id,
+                    verdict,
+                },
+            );
+        })?;
+        handle
+            .emit("test-summary", Summary::new(&verdicts))
+            .map_to_string()?;
+        let submit_on_ac = state.get_config().toggle.submit_on_ac;
+        if verdicts.iter().all(|v| v.status == "Accepted") && submit_on_ac {
             WINDOW
                 .get()
                 .expect("could not find widow")
@@ -89,8 +351,6 @@ pub async fn test(
         handle.emit("set-verdicts", &verdicts).map_to_string()?;
     }
 
-    remove_dir_all(dir).map_to_string()?;
-
     Ok(())
 }
 
@@ -129,117 +389,462 @@ fn compile(language: &Language, dir: &Path) -> Result<bool, String> {
     }
 }
 
-fn run_all(
-    language: &Language,
-    dir: &Path,
-    verdicts: Vec<Verdict>,
-    time_limit: usize,
-) -> Result<Vec<Verdict>, String> {
-    let mut res = vec![];
-    for v in verdicts {
-        res.push(run(language, dir, v, time_limit)?);
+impl Judge {
+    /// Sets up the judge for the current problem, building its interactor and checker.
+    /// The solution is compiled separately.
+    pub fn new(
+        state: &AppState,
+        solution: Executable,
+        source_map: SourceMap,
+    ) -> Result<Self, String> {
+        let interactor = if state.problem.interactive {
+            let program = state
+                .problem
+                .interactor
+                .as_ref()
+                .ok_or("No interactor set for this interactive problem")?;
+            Some(program.build(state, "interactor")?)
+        } else {
+            None
+        };
+        let checker = match &state.problem.checker {
+            Some(checker) => Some(checker.build(state)?),
+            None => None,
+        };
+
+        let settings = state.get_config().judge;
+        Ok(Judge {
+            places: SourceMap::places(&solution.language.source_file),
+            solution,
+            source_map,
+            interactor,
+            checker,
+            comparator: state.problem.comparator.unwrap_or(settings.comparator),
+            epsilon: state.problem.epsilon.unwrap_or(settings.epsilon),
+            time_limit: state.problem.time_limit,
+            memory_limit: state.problem.memory_limit,
+            output_limit: settings.output_limit,
+            parallelism: settings.parallelism,
+        })
     }
-    Ok(res)
-}
 
-fn run(
-    language: &Language,
-    dir: &Path,
-    mut verdict: Verdict,
-    time_limit: usize,
-) -> Result<Verdict, String> {
-    #[cfg(debug_assertions)]
-    println!("dir: {}", dir.to_str().unwrap());
-    let run_cmd = &language.run_cmd;
+    /// Runs the tests on a pool of workers, calling `on_verdict` as soon as each one is done.
+    /// Interactive problems are run one test at a time, and tests that took more than half
+    /// of the time limit are run again on their own so that the other tests don't inflate
+    /// their time.
+    fn run_all(
+        &self,
+        verdicts: Vec<Verdict>,
+        on_verdict: impl Fn(usize, &Verdict) + Sync,
+    ) -> Result<Vec<Verdict>, String> {
+        let run = |index: usize, verdict: Verdict| {
+            let verdict = self.run(verdict)?;
+            on_verdict(index, &verdict);
+            Ok::<_, String>(verdict)
+        };
+
+        let workers = match self.parallelism {
+            0 => thread::available_parallelism().map_or(1, |n| n.get()),
+            n => n,
+        };
+        if workers <= 1 || self.interactor.is_some() {
+            return verdicts
+                .into_iter()
+                .enumerate()
+                .map(|(i, v)| run(i, v))
+                .collect();
+        }
+
+        let next = AtomicUsize::new(0);
+        let results = Mutex::new(vec![None; verdicts.len()]);
+        thread::scope(|s| {
+            for _ in 0..workers.min(verdicts.len()) {
+                s.spawn(|| loop {
+                    let i = next.fetch_add(1, Ordering::Relaxed);
+                    let Some(v) = verdicts.get(i) else {
+                        break;
+                    };
+                    let res = run(i, v.clone());
+                    results.lock().unwrap()[i] = Some(res);
+                });
+            }
+        });
 
-    #[cfg(target_os = "windows")]
-    {
-        if !language.run_cmd_win.is_empty() {
-            run_cmd = &language.run_cmd_win;
+        let mut res = vec![];
+        let results = results.into_inner().unwrap();
+        for (i, (v, r)) in verdicts.into_iter().zip(results).enumerate() {
+            let verdict = r.ok_or("test was not run")??;
+            if 2.0 * verdict.time > self.time_limit as f32 {
+                res.push(run(i, v)?);
+            } else {
+                res.push(verdict);
+            }
         }
+        Ok(res)
     }
 
-    #[cfg(debug_assertions)]
-    println!("run_cmd: {}", run_cmd);
+    /// Compiler errors and stderr of the solution pointing at the files its code comes from.
+    pub fn map_diagnostics(&self, text: &str) -> String {
+        self.source_map.rewrite(text, &self.places)
+    }
 
-    // Create command with platform-specific options
-    #[cfg(windows)]
-    let mut child = Command::new(resolve_path(dir, run_cmd))
-        .current_dir(dir)
-        .args(&language.run_args)
-        .creation_flags(CREATE_NO_WINDOW)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_to_string()?;
+    pub fn run(&self, mut verdict: Verdict) -> Result<Verdict, String> {
+        if let Some(interactor) = &self.interactor {
+            return self.run_interactive(interactor, verdict);
+        }
 
-    #[cfg(not(windows))]
-    let mut child = Command::new(resolve_path(dir, run_cmd))
-        .current_dir(dir)
-        .args(&language.run_args)
-        .stdin(Stdio::piped())
-        .stdout(Stdio::piped())
-        .stderr(Stdio::piped())
-        .spawn()
-        .map_to_string()?;
+        let mut command = self.solution.command();
+        process::limit_memory(&mut command, &self.limits());
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_to_string()?;
+
+        let exceeded = AtomicBool::new(false);
+        let (usage, stdout, stderr) = self.communicate(
+            &mut child,
+            &verdict.input,
+            &exceeded,
+            &self.limits(),
+            || exceeded.load(Ordering::Relaxed),
+        );
+        verdict.output = stdout;
+        verdict.stderr = self.map_diagnostics(&stderr);
+
+        match usage {
+            Ok(usage) => {
+                verdict.time = usage.time;
+                verdict.memory = usage.memory;
+                match usage.exit {
+                    // The solution may die from the closed pipe before it is killed
+                    _ if exceeded.load(Ordering::Relaxed) => {
+                        verdict.status = "Output Limit Exceeded".into();
+                        verdict.status_id = 19;
+                    }
+                    Exit::Finished(exit_status) => {
+                        if !exit_status.success() {
+                            verdict.status_id = 11;
+                            verdict.status = "Runtime Error (NZEC)".into();
+                        } else if let Some(checker) = &self.checker {
+                            self.run_checker(checker, &mut verdict)?;
+                        } else if self.comparator.compare(
+                            &verdict.output,
+                            &verdict.answer,
+                            self.epsilon,
+                        ) {
+                            verdict.status = "Accepted".into();
+                            verdict.status_id = 3;
+                        } else {
+                            verdict.status = "Wrong Answer".into();
+                            verdict.status_id = 4;
+                        }
+                    }
+                    Exit::TimeLimit | Exit::Stopped => {
+                        verdict.status = "Time Limit Exceeded".into();
+                        verdict.status_id = 5;
+                    }
+                    Exit::MemoryLimit => {
+                        verdict.status = "Memory Limit Exceeded".into();
+                        verdict.status_id = 18;
+                    }
+                }
+            }
+            Err(e) => {
+                verdict.stderr = e.to_string();
+                verdict.status_id = 7;
+                verdict.status = "Runtime Error".into();
+            }
+        }
+
+        Ok(verdict)
+    }
 
-    if let Some(mut stdin) = child.stdin.take() {
-        stdin.write_all(verdict.input.as_bytes()).map_to_string()?;
+    /// Feeds `input` to the child and drains its stdout and stderr on separate threads
+    /// while waiting for it, so that neither side can block on a full pipe. Output past
+    /// the output limit raises `exceeded`.
+    fn communicate(
+        &self,
+        child: &mut Child,
+        input: &str,
+        exceeded: &AtomicBool,
+        limits: &Limits,
+        stop: impl FnMut() -> bool,
+    ) -> (io::Result<Usage>, String, String) {
+        let stdin = child.stdin.take();
+        let stdout = child.stdout.take();
+        let stderr = child.stderr.take();
+        let limit = self.output_limit as u64 * 1024 * 1024;
+
+        thread::scope(|s| {
+            s.spawn(move || {
+                if let Some(mut stdin) = stdin {
+                    // Fails with a broken pipe when the child exits without reading everything
+                    let _ = stdin.write_all(input.as_bytes());
+                }
+            });
+            let stdout = s.spawn(move || stdout.map(|p| drain(p, limit, exceeded)));
+            let stderr = s.spawn(move || stderr.map(|p| drain(p, limit, exceeded)));
+
+            let usage = process::wait(child, limits, stop);
+            (
+                usage,
+                stdout.join().ok().flatten().unwrap_or_default(),
+                stderr.join().ok().flatten().unwrap_or_default(),
+            )
+        })
+    }
+
+    /// Runs a helper program such as a generator, which may be slower than the solution, and
+    /// returns its stdout, failing unless it exits successfully.
+    pub fn execute(
+        &self,
+        program: &Executable,
+        args: &[String],
+        input: &str,
+    ) -> Result<String, String> {
+        let mut child = program
+            .command()
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_to_string()?;
+
+        let exceeded = AtomicBool::new(false);
+        let (usage, stdout, stderr) =
+            self.communicate(&mut child, input, &exceeded, &self.helper_limits(), || {
+                exceeded.load(Ordering::Relaxed)
+            });
+
+        match usage.map_to_string()?.exit {
+            _ if exceeded.load(Ordering::Relaxed) => Err("Output Limit Exceeded".into()),
+            Exit::Finished(status) if status.success() => Ok(stdout),
+            Exit::Finished(status) => Err(format!("Runtime Error ({status})\n{stderr}")),
+            Exit::TimeLimit | Exit::Stopped => Err("Time Limit Exceeded".into()),
+            Exit::MemoryLimit => Err("Memory Limit Exceeded".into()),
+        }
+    }
+
+    fn limits(&self) -> Limits {
+        Limits {
+            time: Duration::from_millis(2 * self.time_limit as u64),
+            memory: (self.memory_limit > 0).then_some(self.memory_limit as u64 * 1024),
+            address_space: self.solution.language.cap_address_space,
+        }
+    }
+
+    /// Limits of the checker and other helper programs, only there to stop one that is stuck.
+    fn helper_limits(&self) -> Limits {
+        Limits {
+            time: HELPER_TIME_LIMIT.max(Duration::from_millis(10 * self.time_limit as u64)),
+            memory: None,
+            address_space: false,
+        }
     }
 
-    let start = Instant::now();
-    let status = child.wait_timeout(Duration::from_millis(2 * time_limit as u64));
-    verdict.time = start.elapsed().as_secs_f32() * 1000.0;
+    /// Runs the solution with its stdin and stdout connected to the interactor, which
+    /// decides the verdict through its exit code like a testlib interactor.
+    fn run_interactive(
+        &self,
+        interactor: &Executable,
+        mut verdict: Verdict,
+    ) -> Result<Verdict, String> {
+        // The interactor reads the test from a file and writes its log next to it
+        let id = Uuid::new_v4();
+        let input_file = interactor.dir.join(format!("{id}.in"));
+        let output_file = interactor.dir.join(format!("{id}.out"));
+        let answer_file = interactor.dir.join(format!("{id}.ans"));
+        fs::write(&input_file, &verdict.input).map_to_string()?;
+        fs::write(&answer_file, &verdict.answer).map_to_string()?;
 
-    match status {
-        Ok(Some(exit_status)) => {
-            let mut stdout = String::new();
-            if let Some(mut s) = child.stdout.take() {
-                s.read_to_string(&mut stdout).map_to_string()?;
+        // Both processes have to be done by the same deadline
+        let deadline = Instant::now() + self.limits().time;
+        let mut inter = interactor
+            .command()
+            .args([&input_file, &output_file, &answer_file])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_to_string_mess("Failed to start the interactor")?;
+
+        let to_solution = inter
+            .stdout
+            .take()
+            .ok_or("interactor stdout is unavailable")?;
+        let from_solution = inter
+            .stdin
+            .take()
+            .ok_or("interactor stdin is unavailable")?;
+        let interactor_stderr = inter.stderr.take();
+
+        let mut command = self.solution.command();
+        process::limit_memory(&mut command, &self.limits());
+        let child = command
+            .stdin(Stdio::from(to_solution))
+            .stdout(Stdio::from(from_solution))
+            .stderr(Stdio::piped())
+            .spawn();
+        let mut child = match child {
+            Ok(child) => child,
+            Err(e) => {
+                inter.kill().map_to_string()?;
+                inter.wait().map_to_string()?;
+                return Err(e.to_string());
             }
-            let mut stderr = String::new();
-            if let Some(mut s) = child.stderr.take() {
-                s.read_to_string(&mut stderr).map_to_string()?;
+        };
+
+        // An interactor that already gave up decides the verdict, so stop waiting for it
+        let exceeded = AtomicBool::new(false);
+        let limits = Limits {
+            time: deadline.saturating_duration_since(Instant::now()),
+            ..self.limits()
+        };
+        // The log of the interactor is read all along, so it never blocks on a full pipe
+        let (usage, stderr, interactor_status, message) = thread::scope(|s| {
+            let message = s.spawn(move || {
+                let mut message = String::new();
+                if let Some(mut s) = interactor_stderr {
+                    let _ = s.read_to_string(&mut message);
+                }
+                message
+            });
+            let (usage, _, stderr) = self.communicate(&mut child, "", &exceeded, &limits, || {
+                exceeded.load(Ordering::Relaxed)
+                    || matches!(inter.try_wait(), Ok(Some(status)) if !status.success())
+            });
+            let mut status = inter
+                .wait_timeout(deadline.saturating_duration_since(Instant::now()))
+                .map_to_string();
+            if let Ok(None) = status {
+                status = inter
+                    .kill()
+                    .and_then(|_| inter.wait())
+                    .map(|_| None)
+                    .map_to_string();
             }
+            (usage, stderr, status, message.join().unwrap_or_default())
+        });
+        let usage = usage.map_to_string()?;
+        verdict.time = usage.time;
+        verdict.memory = usage.memory;
+        let interactor_status = interactor_status?;
 
-            verdict.output = stdout;
-            verdict.stderr = stderr;
+        verdict.output = fs::read_to_string(&output_file).unwrap_or_default();
+        verdict.stderr = self.map_diagnostics(&stderr);
+        verdict.message = message.trim().to_string();
 
-            if !exit_status.success() {
+        for f in [input_file, output_file, answer_file] {
+            let _ = fs::remove_file(f);
+        }
+
+        let interactor_code = interactor_status.and_then(|s| s.code());
+        match usage.exit {
+            _ if exceeded.load(Ordering::Relaxed) => {
+                verdict.status = "Output Limit Exceeded".into();
+                verdict.status_id = 19;
+            }
+            Exit::Finished(status)
+                if !status.success() && interactor_code.is_none_or(|c| c == 0) =>
+            {
                 verdict.status_id = 11;
                 verdict.status = "Runtime Error (NZEC)".into();
-            } else {
-                if check(&verdict.answer, &verdict.output) {
-                    verdict.status = "Accepted".into();
-                    verdict.status_id = 3;
-                } else {
-                    verdict.status = "Wrong Answer".into();
-                    verdict.status_id = 4;
-                }
             }
+            // A solution that used little CPU time was waiting for the interactor
+            Exit::TimeLimit if usage.cpu.is_some_and(|cpu| cpu <= self.time_limit as f32) => {
+                verdict.status = "Idleness Limit Exceeded".into();
+                verdict.status_id = 15;
+            }
+            Exit::TimeLimit => {
+                verdict.status = "Time Limit Exceeded".into();
+                verdict.status_id = 5;
+            }
+            Exit::MemoryLimit => {
+                verdict.status = "Memory Limit Exceeded".into();
+                verdict.status_id = 18;
+            }
+            _ => match (interactor_code, &self.checker) {
+                // The checker gets the interactor's log as the output
+                (Some(0), Some(checker)) => self.run_checker(checker, &mut verdict)?,
+                _ => set_testlib_status(&mut verdict, interactor_code),
+            },
         }
-        Ok(None) => {
-            child.kill().map_to_string()?;
-            child.wait().map_to_string()?; // Wait to reap child
-            verdict.status = "Time Limit Exceeded".into();
-            verdict.status_id = 5;
+
+        Ok(verdict)
+    }
+
+    /// Runs the checker as `checker input output answer`, taking the verdict from its exit
+    /// code and the message from its stderr.
+    fn run_checker(&self, checker: &Executable, verdict: &mut Verdict) -> Result<(), String> {
+        let id = Uuid::new_v4();
+        let input_file = checker.dir.join(format!("{id}.in"));
+        let output_file = checker.dir.join(format!("{id}.out"));
+        let answer_file = checker.dir.join(format!("{id}.ans"));
+        fs::write(&input_file, &verdict.input).map_to_string()?;
+        fs::write(&output_file, &verdict.output).map_to_string()?;
+        fs::write(&answer_file, &verdict.answer).map_to_string()?;
+
+        let child = checker
+            .command()
+            .args([&input_file, &output_file, &answer_file])
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_to_string_mess("Failed to run the checker");
+        let result = child.map(|mut child| {
+            let exceeded = AtomicBool::new(false);
+            self.communicate(&mut child, "", &exceeded, &self.helper_limits(), || false)
+        });
+
+        for f in [input_file, output_file, answer_file] {
+            let _ = fs::remove_file(f);
         }
-        Err(e) => {
-            verdict.stderr = e.to_string();
-            verdict.status_id = 7;
-            verdict.status = "Runtime Error".into();
+
+        let (usage, _, stderr) = result?;
+        verdict.message = stderr.trim().to_string();
+        match usage.map_to_string_mess("Failed to run the checker")?.exit {
+            Exit::Finished(status) => set_testlib_status(verdict, status.code()),
+            _ => {
+                verdict.status_id = 13;
+                verdict.status = "Judgement Failed".into();
+                verdict.message =
+                    format!("The checker exceeded its time limit\n{}", verdict.message)
+                        .trim()
+                        .to_string();
+            }
         }
+
+        Ok(())
     }
+}
 
-    Ok(verdict)
+/// Reads the pipe until it closes, keeping at most `limit` bytes. Reading stops and
+/// `exceeded` is raised once the pipe yields more than that.
+fn drain(pipe: impl Read, limit: u64, exceeded: &AtomicBool) -> String {
+    let mut buf = Vec::new();
+    let _ = pipe.take(limit + 1).read_to_end(&mut buf);
+    if buf.len() as u64 > limit {
+        exceeded.store(true, Ordering::Relaxed);
+        buf.truncate(limit as usize);
+    }
+    String::from_utf8_lossy(&buf).into_owned()
 }
 
-fn check(output: &String, answer: &String) -> bool {
-    output
-        .trim()
-        .split('\n')
-        .map(|x| x.trim())
-        .zip(answer.trim().split('\n').map(|x| x.trim()))
-        .all(|(x, y)| x == y)
+/// Maps the exit code of a testlib checker or interactor onto the verdict.
+fn set_testlib_status(verdict: &mut Verdict, code: Option<i32>) {
+    let (status_id, status) = match code {
+        Some(0) => (3, "Accepted"),
+        Some(1) => (4, "Wrong Answer"),
+        // presentation error, "dirt" and unexpected end of file
+        Some(2) | Some(4) | Some(8) => (16, "Presentation Error"),
+        // points and partially correct
+        Some(7) | Some(16..) => (17, "Partially Correct"),
+        _ => (13, "Judgement Failed"),
+    };
+    verdict.status_id = status_id;
+    verdict.status = status.into();
 }
@@ -1,8 +1,10 @@
-use crate::{state::AppState, utils::ResultTrait};
+use crate::{config::ConfigError, state::AppState, utils::ResultTrait, watch};
+use notify::{RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::{
     collections::HashMap,
+    path::{Path, PathBuf},
     process::{Command, Stdio},
     sync::Mutex,
     time::Duration,
@@ -38,6 +40,9 @@ pub struct Language {
     pub check_args: Vec<String>,
     #[serde(skip_serializing)]
     pub comment: String,
+    /// Cap the address space of the solution near the memory limit
+    #[serde(skip_serializing)]
+    pub cap_address_space: bool,
 }
 
 impl Language {
@@ -74,19 +79,20 @@ impl Language {
     }
 }
 
-#[tauri::command]
-pub async fn get_languages(
-    state: State<'_, Mutex<AppState>>,
-    handle: tauri::AppHandle,
-) -> Result<Vec<Language>, String> {
-    if state.lock().unwrap().languages.is_empty() {
-        let path = handle
-            .path()
-            .resolve("Languages.toml", BaseDirectory::Config)
-            .map_to_string_mess("Failed to resolve config path")?;
+pub fn get_languages_path(handle: &tauri::AppHandle) -> Result<PathBuf, String> {
+    handle
+        .path()
+        .resolve("Languages.toml", BaseDirectory::Config)
+        .map_to_string_mess("Failed to resolve config path")
+}
+
+/// Reads `Languages.toml`, keeping only the languages whose toolchain is installed.
+pub fn read_languages(handle: &tauri::AppHandle) -> Result<HashMap<String, Language>, ConfigError> {
+    let path = get_languages_path(handle).map_err(|e| ConfigError::new(Path::new(""), e))?;
 
-        // If config file doesn't exist, copy from resources
-        if !path.exists() {
+    // If config file doesn't exist, copy from resources
+    if !path.exists() {
+        let copy = || -> Result<(), String> {
             let resource_path = handle
                 .path()
                 .resolve("Languages.toml", BaseDirectory::Resource)
@@ -100,26 +106,58 @@ pub async fn get_languages(
 
             fs::copy(&resource_path, &path)
                 .map_to_string_mess("Failed to copy to config directory")?;
-        }
-
-        // Read from config directory
-        let content =
-            fs::read_to_string(&path).map_to_string_mess(&format!("Error reading {:?}", path))?;
-        let languages: HashMap<String, Language> =
-            toml::from_str(&content).map_to_string_mess("Error parsing Languages.toml")?;
-
-        state.lock().unwrap().languages = languages.into_iter().filter(|(_, v)| v.check()).collect()
+            Ok(())
+        };
+        copy().map_err(|e| ConfigError::new(&path, e))?;
     }
 
-    let languages_map = state.lock().unwrap().languages.clone();
+    // Read from config directory
+    let content = fs::read_to_string(&path).map_err(|e| ConfigError::new(&path, e))?;
+    let languages: HashMap<String, Language> =
+        toml::from_str(&content).map_err(|e| ConfigError::parse(&path, &content, &e))?;
+
+    Ok(languages
+        .into_iter()
+        .filter(|(_, v)| v.check())
+        .map(|(id, language)| {
+            let language = Language {
+                id: id.parse().unwrap_or(0),
+                ..language
+            };
+            (id, language)
+        })
+        .collect())
+}
+
+/// The languages sorted by id, as the frontend shows them.
+pub fn list_languages(languages: &HashMap<String, Language>) -> Vec<Language> {
+    let mut languages = languages.values().cloned().collect::<Vec<_>>();
+    languages.sort_by_key(|language| language.id);
+    languages
+}
 
-    let mut languages = vec![];
-    for (id, mut language) in languages_map {
-        language.id = id.parse().unwrap_or(0);
-        languages.push(language);
+#[tauri::command]
+pub async fn get_languages(
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<Vec<Language>, String> {
+    if state.lock().unwrap().languages.is_empty() {
+        let languages = read_languages(&handle).map_to_string()?;
+        state.lock().unwrap().languages = languages;
+
+        // Pick up later changes of the file
+        let path = get_languages_path(&handle)?;
+        watch::set_languages(&path);
+        if let (Some(watcher), Some(dir)) = (&state.lock().unwrap().watcher, path.parent()) {
+            watcher
+                .write()
+                .unwrap()
+                .watch(dir, RecursiveMode::NonRecursive)
+                .map_to_string()?;
+        }
     }
 
-    Ok(languages)
+    Ok(list_languages(&state.lock().unwrap().languages))
 }
 
 #[tauri::command]
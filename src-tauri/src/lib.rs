@@ -1,25 +1,32 @@
+mod bundle;
+mod compare;
 mod config;
 mod info;
 mod judge;
 mod language;
+mod process;
 mod state;
+mod stress;
 mod submit;
 mod utils;
+mod validate;
+mod watch;
 
 use actix_web::{web, App, HttpServer};
-use config::read_config;
+use config::{get_config_schema, get_effective_config, read_config, set_config_value};
 use info::*;
 use judge::*;
 use language::*;
 use notify::Event;
 use state::*;
+use stress::*;
+use validate::validate_config;
 use std::{
     sync::{mpsc, Arc, Mutex, OnceLock, RwLock},
     thread,
-    time::{self, Duration},
 };
 use submit::*;
-use tauri::{AppHandle, Emitter, Manager, WebviewWindow};
+use tauri::{AppHandle, Manager, WebviewWindow};
 
 use crate::utils::ResultTrait;
 
@@ -36,23 +43,7 @@ pub fn run() {
             let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
             let watcher = notify::recommended_watcher(tx).map_to_string()?;
 
-            let handle = thread::spawn(move || {
-                let mut previous = time::Instant::now();
-
-                let window = WINDOW.get().expect("window-is-unavailable");
-                for res in rx {
-                    let now = time::Instant::now();
-                    if now.duration_since(previous) < Duration::from_secs(1) {
-                        continue;
-                    }
-                    println!("got something");
-                    match res {
-                        Ok(_event) => window.emit("test", 0).unwrap(),
-                        Err(e) => println!("watch error: {:?}", e),
-                    }
-                    previous = now;
-                }
-            });
+            let handle = thread::spawn(move || watch::handle_events(rx));
 
             drop(handle);
 
@@ -82,7 +73,7 @@ pub fn run() {
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
         .plugin(tauri_plugin_single_instance::init(|app, _args, _cwd| {
-            let _ = show_window(app);
+            show_window(app);
         }))
         .invoke_handler(tauri::generate_handler![
             get_directory,
@@ -92,14 +83,37 @@ pub fn run() {
             get_languages,
             set_problem,
             get_problem,
+            get_contest,
+            switch_problem,
             set_verdicts,
             get_verdicts,
+            add_test,
+            update_test,
+            delete_test,
+            duplicate_test,
+            reorder_tests,
+            set_test_source,
             create_file,
             copy_code,
             read_config,
+            get_effective_config,
+            get_config_schema,
+            set_config_value,
+            validate_config,
             save_state,
+            get_inbox,
+            accept_problem,
+            dismiss_problem,
             submit_solution,
             test,
+            set_interactor,
+            set_checker,
+            set_comparator,
+            set_generator,
+            set_brute,
+            stress,
+            stop_stress,
+            minimize,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
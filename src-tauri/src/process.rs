@@ -0,0 +1,278 @@
+use std::{
+    io,
+    process::{Child, Command, ExitStatus},
+    thread,
+    time::{Duration, Instant},
+};
+
+const POLL_INTERVAL: Duration = Duration::from_millis(1);
+
+/// Why a child stopped running.
+pub enum Exit {
+    /// It exited on its own
+    Finished(ExitStatus),
+    /// It was killed for running longer than the time limit
+    TimeLimit,
+    /// It used more memory than the memory limit, and was killed unless it died on its own
+    MemoryLimit,
+    /// It was killed because the caller asked for it
+    Stopped,
+}
+
+/// Resources used by a child until it stopped.
+pub struct Usage {
+    pub exit: Exit,
+    /// Wall time in milliseconds
+    pub time: f32,
+    /// Peak resident set size in kilobytes, 0 where it can't be measured
+    pub memory: f32,
+    /// User and system CPU time in milliseconds, None where it can't be measured
+    pub cpu: Option<f32>,
+}
+
+pub struct Limits {
+    pub time: Duration,
+    /// Memory limit in kilobytes
+    pub memory: Option<u64>,
+    /// Whether the address space is capped too, see `limit_memory`
+    pub address_space: bool,
+}
+
+/// Address space in kilobytes a capped child may map beyond the memory limit, for its
+/// libraries and stack.
+const ADDRESS_SPACE_SLACK: u64 = 64 * 1024;
+
+/// Share of the memory limit from which a child killed by a signal is taken to have run out
+/// of memory.
+const NEAR_LIMIT: f32 = 0.9;
+
+const SIGABRT: i32 = 6;
+
+/// Caps the address space of the command's child near the memory limit when the limits ask
+/// for it, so that allocating past the limit fails at once. Runtimes like the JVM and V8
+/// reserve far more than they use and can't start under such a cap, so it is opt-in.
+pub fn limit_memory(command: &mut Command, limits: &Limits) {
+    if let (true, Some(limit)) = (limits.address_space, limits.memory) {
+        imp::limit_memory(command, (limit + ADDRESS_SPACE_SLACK) * 1024);
+    }
+}
+
+/// Waits for the child while enforcing the limits, killing it as soon as it exceeds one
+/// or `stop` returns true. The child is always reaped when this returns `Ok`.
+pub fn wait(
+    child: &mut Child,
+    limits: &Limits,
+    mut stop: impl FnMut() -> bool,
+) -> io::Result<Usage> {
+    let start = Instant::now();
+    let (mut exit, memory, cpu) = imp::wait(child, limits, start, &mut stop)?;
+    let time = start.elapsed().as_secs_f32() * 1000.0;
+
+    // The memory can spike between two polls, or the child can die failing to allocate
+    if let Exit::Finished(status) = &exit {
+        if exceeded_memory(signal(status), memory, limits) {
+            exit = Exit::MemoryLimit;
+        }
+    }
+
+    Ok(Usage {
+        exit,
+        time,
+        memory,
+        cpu,
+    })
+}
+
+/// Whether a child that finished with the peak memory in kilobytes went over the memory
+/// limit. A failed allocation kills it with a signal, which is put down to the memory when
+/// the peak got near the limit, or when it aborted under the address space cap, where
+/// allocations fail before the memory is used.
+fn exceeded_memory(signal: Option<i32>, peak: f32, limits: &Limits) -> bool {
+    let Some(limit) = limits.memory else {
+        return false;
+    };
+    let limit = limit as f32;
+    peak > limit
+        || signal.is_some_and(|signal| {
+            peak >= NEAR_LIMIT * limit || (limits.address_space && signal == SIGABRT)
+        })
+}
+
+#[cfg(unix)]
+fn signal(status: &ExitStatus) -> Option<i32> {
+    std::os::unix::process::ExitStatusExt::signal(status)
+}
+
+#[cfg(not(unix))]
+fn signal(_status: &ExitStatus) -> Option<i32> {
+    None
+}
+
+/// Uses `wait4` to get the peak memory of the child, `/proc` to watch it while it runs, and
+/// `setrlimit` to cap its address space.
+#[cfg(target_os = "linux")]
+mod imp {
+    use super::*;
+    use std::{
+        fs,
+        os::unix::process::{CommandExt, ExitStatusExt},
+    };
+
+    pub fn limit_memory(command: &mut Command, bytes: u64) {
+        let limit = libc::rlimit {
+            rlim_cur: bytes as libc::rlim_t,
+            rlim_max: bytes as libc::rlim_t,
+        };
+        // SAFETY: setrlimit is async-signal-safe and the closure doesn't allocate
+        unsafe {
+            command.pre_exec(move || match libc::setrlimit(libc::RLIMIT_AS, &limit) {
+                -1 => Err(io::Error::last_os_error()),
+                _ => Ok(()),
+            });
+        }
+    }
+
+    pub fn wait(
+        child: &mut Child,
+        limits: &Limits,
+        start: Instant,
+        stop: &mut impl FnMut() -> bool,
+    ) -> io::Result<(Exit, f32, Option<f32>)> {
+        let pid = child.id() as libc::pid_t;
+        let mut peak = 0;
+
+        loop {
+            if let Some((status, rusage)) = wait4(pid, libc::WNOHANG)? {
+                let peak = peak.max(rusage.ru_maxrss as u64);
+                return Ok((Exit::Finished(status), peak as f32, cpu(&rusage)));
+            }
+
+            let memory = resident(pid);
+            peak = peak.max(memory);
+            let exit = if start.elapsed() > limits.time {
+                Exit::TimeLimit
+            } else if limits.memory.is_some_and(|limit| memory > limit) {
+                Exit::MemoryLimit
+            } else if stop() {
+                Exit::Stopped
+            } else {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+
+            child.kill()?;
+            // Not reaped through `child`, which must not be waited on afterwards
+            let (_, rusage) = wait4(pid, 0)?.ok_or(io::ErrorKind::Other)?;
+            let peak = peak.max(rusage.ru_maxrss as u64);
+            return Ok((exit, peak as f32, cpu(&rusage)));
+        }
+    }
+
+    /// Current resident set size of the process in kilobytes.
+    fn resident(pid: libc::pid_t) -> u64 {
+        // SAFETY: sysconf has no preconditions
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        fs::read_to_string(format!("/proc/{pid}/statm"))
+            .ok()
+            .and_then(|statm| statm.split_whitespace().nth(1)?.parse::<u64>().ok())
+            .map_or(0, |pages| pages * page_size / 1024)
+    }
+
+    /// User and system CPU time in milliseconds.
+    fn cpu(rusage: &libc::rusage) -> Option<f32> {
+        let ms = |t: libc::timeval| t.tv_sec as f32 * 1000.0 + t.tv_usec as f32 / 1000.0;
+        Some(ms(rusage.ru_utime) + ms(rusage.ru_stime))
+    }
+
+    fn wait4(
+        pid: libc::pid_t,
+        options: libc::c_int,
+    ) -> io::Result<Option<(ExitStatus, libc::rusage)>> {
+        let mut status = 0;
+        // SAFETY: rusage is a plain C struct for which all zeroes is a valid value
+        let mut rusage: libc::rusage = unsafe { std::mem::zeroed() };
+        loop {
+            // SAFETY: both pointers are valid for the duration of the call
+            match unsafe { libc::wait4(pid, &mut status, options, &mut rusage) } {
+                0 => return Ok(None),
+                -1 => {
+                    let err = io::Error::last_os_error();
+                    if err.kind() != io::ErrorKind::Interrupted {
+                        return Err(err);
+                    }
+                }
+                _ => return Ok(Some((ExitStatus::from_raw(status), rusage))),
+            }
+        }
+    }
+}
+
+/// Memory and CPU time are not measured on other platforms, so only the time limit is
+/// enforced.
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use super::*;
+
+    pub fn limit_memory(_command: &mut Command, _bytes: u64) {}
+
+    pub fn wait(
+        child: &mut Child,
+        limits: &Limits,
+        start: Instant,
+        stop: &mut impl FnMut() -> bool,
+    ) -> io::Result<(Exit, f32, Option<f32>)> {
+        loop {
+            if let Some(status) = child.try_wait()? {
+                return Ok((Exit::Finished(status), 0.0, None));
+            }
+
+            let exit = if start.elapsed() > limits.time {
+                Exit::TimeLimit
+            } else if stop() {
+                Exit::Stopped
+            } else {
+                thread::sleep(POLL_INTERVAL);
+                continue;
+            };
+
+            child.kill()?;
+            child.wait()?;
+            return Ok((exit, 0.0, None));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits(memory: Option<u64>, address_space: bool) -> Limits {
+        Limits {
+            time: Duration::from_secs(1),
+            memory,
+            address_space,
+        }
+    }
+
+    #[test]
+    fn peak_over_the_limit() {
+        assert!(exceeded_memory(None, 300.0, &limits(Some(256), false)));
+        assert!(!exceeded_memory(None, 200.0, &limits(Some(256), false)));
+        assert!(!exceeded_memory(None, 300.0, &limits(None, false)));
+    }
+
+    #[test]
+    fn signal_near_the_limit() {
+        // SIGSEGV
+        assert!(exceeded_memory(Some(11), 250.0, &limits(Some(256), false)));
+        assert!(!exceeded_memory(Some(11), 10.0, &limits(Some(256), false)));
+        assert!(!exceeded_memory(None, 250.0, &limits(Some(256), false)));
+    }
+
+    #[test]
+    fn abort_under_the_address_space_cap() {
+        assert!(exceeded_memory(Some(SIGABRT), 10.0, &limits(Some(256), true)));
+        assert!(!exceeded_memory(Some(SIGABRT), 10.0, &limits(Some(256), false)));
+        assert!(!exceeded_memory(Some(11), 10.0, &limits(Some(256), true)));
+    }
+}
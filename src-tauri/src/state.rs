@@ -1,19 +1,26 @@
-use crate::{config::Config, info::Problem, judge::Verdict, utils::ResultTrait, Language};
+use crate::{
+    bundle::{prune, SourceMap},
+    config::{Config, ConfigError, LayeredConfig, ProblemContext},
+    info::{Contest, Problem},
+    judge::{Executable, TestSource, Verdict},
+    utils::ResultTrait,
+    watch, Language, WINDOW,
+};
 use chrono::Local;
-use notify::{FsEventWatcher, RecursiveMode, Watcher};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
 use serde::{Deserialize, Serialize};
 use std::{
-    collections::HashMap,
-    fs::{create_dir_all, File},
+    collections::{HashMap, HashSet},
+    fs::{self, create_dir_all, File},
     io::{BufReader, Write},
-    ops::Deref,
-    path::PathBuf,
+    path::{Path, PathBuf},
     process::Command,
     str::FromStr,
     sync::{Arc, Mutex, RwLock},
     time,
 };
-use tauri::{Manager, State};
+use tauri::{Emitter, Manager, State};
+use uuid::Uuid;
 use wait_timeout::ChildExt;
 
 // Windows-specific imports
@@ -24,20 +31,56 @@ use arboard::Clipboard;
 #[cfg(windows)]
 const CREATE_NO_WINDOW: u32 = 0x08000000; // Prevents opening a new window
 
+/// Name of the file next to the `N.in`/`N.ans` files of a problem holding everything else
+const TESTS_METADATA: &str = "tests.json";
+
+#[derive(Default, Serialize, Deserialize)]
+struct TestsMetadata {
+    #[serde(default)]
+    problem: Problem,
+    /// The i-th entry belongs to the test in `{i + 1}.in`
+    #[serde(default)]
+    tests: Vec<TestMetadata>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct TestMetadata {
+    id: String,
+    source: TestSource,
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct InboxEntry {
+    pub id: String,
+    pub problem: Problem,
+    pub verdicts: Vec<Verdict>,
+    /// When it was parsed, formatted like "2024/12/25 14:07"
+    pub received: String,
+}
+
 #[derive(Default, Serialize, Deserialize, Clone)]
 pub struct AppState {
     pub directory: PathBuf,
     pub language_id: usize,
+    /// The global config merged with the workspace one
     #[serde(default, skip)]
     pub config: Config,
     #[serde(default, skip)]
-    pub languages: HashMap<String, Language>,
+    pub config_layers: LayeredConfig,
     #[serde(default, skip)]
+    pub languages: HashMap<String, Language>,
+    #[serde(default)]
     pub problem: Problem,
+    /// The last batch of problems parsed together
+    #[serde(default)]
+    pub contest: Option<Contest>,
+    /// Parsed problems waiting to be opened
+    #[serde(default)]
+    pub inbox: Vec<InboxEntry>,
     #[serde(default, skip)]
     pub verdicts: Vec<Verdict>,
     #[serde(default, skip)]
-    pub watcher: Option<Arc<RwLock<FsEventWatcher>>>,
+    pub watcher: Option<Arc<RwLock<RecommendedWatcher>>>,
 }
 
 impl AppState {
@@ -59,6 +102,162 @@ impl AppState {
         Ok(res)
     }
 
+    /// Writes the state to `cp_state.json` in the app config directory.
+    pub fn save(&self, handle: &tauri::AppHandle) -> Result<(), String> {
+        let mut file_path = handle.path().app_config_dir().map_to_string()?;
+        file_path.push("cp_state.json");
+
+        let mut f = File::create(file_path).map_to_string()?;
+        f.write_fmt(format_args!(
+            "{}",
+            serde_json::to_string(self).map_to_string()?
+        ))
+        .map_to_string()?;
+
+        Ok(())
+    }
+
+    /// Makes the problem the current one. A problem opened before gets back its saved tests,
+    /// otherwise it starts with the given ones.
+    pub fn open_problem(&mut self, problem: Problem, verdicts: Vec<Verdict>) -> Result<(), String> {
+        self.problem = problem;
+        if !self.load_tests().unwrap_or(false) {
+            self.verdicts = verdicts;
+            self.save_tests()?;
+        }
+        Ok(())
+    }
+
+    /// Keeps a parsed problem in the inbox until it is accepted or dismissed.
+    pub fn receive_problem(&mut self, problem: Problem, verdicts: Vec<Verdict>) {
+        self.inbox.push(InboxEntry {
+            id: Uuid::new_v4().to_string(),
+            problem,
+            verdicts,
+            received: Local::now().format("%Y/%m/%d %H:%M").to_string(),
+        });
+    }
+
+    /// The config of the current problem, see [`AppState::get_config_of`].
+    pub fn get_config(&self) -> Config {
+        self.get_config_of(&self.problem)
+    }
+
+    /// The global and workspace config, then the `config.toml` files in the directories
+    /// between the workspace and the problem's solution file, then the language profile.
+    /// When an override file is broken, it is reported and only the global and workspace
+    /// config are used.
+    pub fn get_config_of(&self, problem: &Problem) -> Config {
+        let config = self
+            .get_layers(problem)
+            .and_then(|layers| {
+                layers
+                    .get_config()
+                    .map_err(|e| ConfigError::new(Path::new("config.toml"), e))
+            })
+            .unwrap_or_else(|error| {
+                if let Some(window) = WINDOW.get() {
+                    let _ = window.emit("config-reloaded", watch::Reload::Error { error });
+                }
+                self.config.clone()
+            });
+        match self.get_language() {
+            Ok(language) => config.for_language(&language),
+            Err(_) => config,
+        }
+    }
+
+    /// The layers of the global and workspace config with the override files of the problem.
+    pub fn get_layers(&self, problem: &Problem) -> Result<LayeredConfig, ConfigError> {
+        let mut layers = self.config_layers.clone();
+        for path in self.get_override_paths(problem) {
+            layers.merge_file(&path)?;
+        }
+        Ok(layers)
+    }
+
+    /// Possible override files of the problem, from the outermost directory inwards.
+    pub fn get_override_paths(&self, problem: &Problem) -> Vec<PathBuf> {
+        let Some(dir) = self
+            .get_file_path(problem)
+            .ok()
+            .and_then(|path| path.parent().map(Path::to_path_buf))
+        else {
+            return vec![];
+        };
+
+        let mut paths = dir
+            .ancestors()
+            .take_while(|d| *d != self.directory && d.starts_with(&self.directory))
+            .map(|d| d.join("config.toml"))
+            .collect::<Vec<_>>();
+        paths.reverse();
+        paths
+    }
+
+    /// Solution file of the problem. Only the global and workspace config decide it.
+    pub fn get_file_path(&self, problem: &Problem) -> Result<PathBuf, String> {
+        let config = match self.get_language() {
+            Ok(language) => self.config.for_language(&language),
+            Err(_) => self.config.clone(),
+        };
+        config.get_file_path(&self.get_context(problem), &self.directory)
+    }
+
+    /// The code of the current problem's solution, ready to be compiled.
+    pub fn get_final_code(&self) -> Result<String, String> {
+        Ok(self.get_mapped_code()?.0)
+    }
+
+    /// The solution in a directory of its own, and where its lines come from to point
+    /// diagnostics at the files. Pruned code comes already compiled.
+    pub fn build_solution(&self) -> Result<(Executable, SourceMap), String> {
+        let (code, map, solution) = self.get_mapped_code()?;
+        let solution = match solution {
+            Some(solution) => solution,
+            None => Executable::new(self.get_language()?, &code)?,
+        };
+        Ok((solution, map))
+    }
+
+    /// The final code, where its lines come from, and the pruned code compiled if it was
+    /// checked.
+    fn get_mapped_code(&self) -> Result<(String, SourceMap, Option<Executable>), String> {
+        let config = self.get_config();
+        let (code, map) =
+            config.get_final_code(&self.get_context(&self.problem), &self.directory)?;
+        if !config.code.prune {
+            return Ok((code, map, None));
+        }
+        let language = self.get_language()?;
+        let Some((pruned, removed)) = prune(&code, &language.source_file) else {
+            return Ok((code, map, None));
+        };
+        // Uses are only guessed from names, so the pruned code is checked before it is used
+        let solution = Executable::new(language, &pruned)?;
+        match solution.compile() {
+            Ok(_) => Ok((pruned, map.without(&code, &removed), Some(solution))),
+            Err(e) => {
+                if let Some(window) = WINDOW.get() {
+                    let _ = window.emit(
+                        "warning",
+                        format!("The pruned code doesn't compile, the whole code is used\n{e}"),
+                    );
+                }
+                Ok((code, map, None))
+            }
+        }
+    }
+
+    /// What the filename and template of a problem are rendered with.
+    pub fn get_context(&self, problem: &Problem) -> ProblemContext {
+        ProblemContext::new(
+            problem,
+            self.get_language().ok().as_ref(),
+            &self.config.author,
+        )
+    }
+
     pub fn get_language(&self) -> Result<Language, String> {
         let language = self
             .languages
@@ -67,6 +266,154 @@ impl AppState {
             .clone();
         Ok(language)
     }
+
+    /// Tests of a problem are kept in `tests/<solution name>/` next to its solution file.
+    pub fn get_tests_dir(&self, problem: &Problem) -> Result<PathBuf, String> {
+        let file_path = self.get_file_path(problem)?;
+        let name = file_path.file_stem().ok_or("invalid file path")?;
+        let parent = file_path.parent().ok_or("invalid file path")?;
+        Ok(parent.join("tests").join(name))
+    }
+
+    /// Writes the tests and the problem to the tests directory, replacing what was there.
+    pub fn save_tests(&self) -> Result<(), String> {
+        self.save_tests_of(&self.problem, &self.verdicts)
+    }
+
+    pub fn save_tests_of(&self, problem: &Problem, verdicts: &[Verdict]) -> Result<(), String> {
+        // Nothing was parsed yet
+        if problem.url.is_empty() {
+            return Ok(());
+        }
+
+        let dir = self.get_tests_dir(problem)?;
+        create_dir_all(&dir).map_to_string()?;
+
+        for (i, verdict) in verdicts.iter().enumerate() {
+            fs::write(dir.join(format!("{}.in", i + 1)), &verdict.input).map_to_string()?;
+            fs::write(dir.join(format!("{}.ans", i + 1)), &verdict.answer).map_to_string()?;
+        }
+        for (number, path) in numbered_files(&dir)? {
+            if number > verdicts.len() {
+                fs::remove_file(path).map_to_string()?;
+            }
+        }
+
+        let metadata = TestsMetadata {
+            problem: problem.clone(),
+            tests: verdicts
+                .iter()
+                .map(|v| TestMetadata {
+                    id: v.id.clone(),
+                    source: v.source,
+                })
+                .collect(),
+        };
+        fs::write(
+            dir.join(TESTS_METADATA),
+            serde_json::to_string_pretty(&metadata).map_to_string()?,
+        )
+        .map_to_string()
+    }
+
+    /// Replaces the tests and the problem with the ones saved for the current problem.
+    /// Returns false when nothing was saved for it.
+    pub fn load_tests(&mut self) -> Result<bool, String> {
+        let Some((problem, verdicts)) = self.read_tests(&self.problem)? else {
+            return Ok(false);
+        };
+        self.problem = problem;
+        self.verdicts = verdicts;
+        Ok(true)
+    }
+
+    /// The problem and the tests saved for a problem, if there are any.
+    pub fn read_tests(&self, problem: &Problem) -> Result<Option<(Problem, Vec<Verdict>)>, String> {
+        if problem.url.is_empty() {
+            return Ok(None);
+        }
+
+        let dir = self.get_tests_dir(problem)?;
+        let metadata_path = dir.join(TESTS_METADATA);
+        if !metadata_path.exists() {
+            return Ok(None);
+        }
+        let metadata: TestsMetadata =
+            serde_json::from_str(&fs::read_to_string(metadata_path).map_to_string()?)
+                .map_to_string_mess("Error parsing tests.json:")?;
+
+        let mut inputs = numbered_files(&dir)?
+            .into_iter()
+            .filter(|(_, path)| path.extension().is_some_and(|ext| ext == "in"))
+            .collect::<Vec<_>>();
+        inputs.sort();
+
+        let mut verdicts = vec![];
+        for (number, path) in inputs {
+            let input = fs::read_to_string(&path).map_to_string()?;
+            // Tests added by hand may come without an answer or metadata
+            let answer = fs::read_to_string(path.with_extension("ans")).unwrap_or_default();
+            let mut verdict = Verdict::new(input, answer);
+            if let Some(test) = number.checked_sub(1).and_then(|i| metadata.tests.get(i)) {
+                verdict.id = test.id.clone();
+                verdict.source = test.source;
+            }
+            verdicts.push(verdict);
+        }
+
+        Ok(Some((metadata.problem, verdicts)))
+    }
+
+    /// Creates the solution file of a problem from the template unless it exists already.
+    pub fn write_solution_file(&self, problem: &Problem) -> Result<PathBuf, String> {
+        let config = self.get_config_of(problem);
+        let context = self.get_context(problem);
+
+        let file_path = self.get_file_path(problem)?;
+        create_dir_all(file_path.parent().ok_or("invalid file path".to_string())?)
+            .map_to_string()?;
+
+        if !file_path.exists() {
+            let header = config.get_header(&context)?;
+            let template = config.get_template(&context, &self.directory)?;
+            let mut f = File::create_new(&file_path).map_to_string()?;
+            f.write_fmt(format_args!("{}{}", header, template))
+                .map_to_string()?;
+        }
+
+        Ok(file_path)
+    }
+
+    fn get_test_index(&self, id: &str) -> Result<usize, String> {
+        self.verdicts
+            .iter()
+            .position(|v| v.id == id)
+            .ok_or(format!("test {id} not found"))
+    }
+
+    /// Saves the tests after they were changed and sends them to the frontend.
+    fn tests_changed(&self, handle: &tauri::AppHandle) -> Result<(), String> {
+        self.save_tests()?;
+        handle.emit("set-verdicts", &self.verdicts).map_to_string()
+    }
+}
+
+/// The `N.in` and `N.ans` files in the directory, with their N.
+fn numbered_files(dir: &Path) -> Result<Vec<(usize, PathBuf)>, String> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir).map_to_string()? {
+        let path = entry.map_to_string()?.path();
+        let is_test = path
+            .extension()
+            .is_some_and(|ext| ext == "in" || ext == "ans");
+        let number = path
+            .file_stem()
+            .and_then(|stem| stem.to_str()?.parse::<usize>().ok());
+        if let (true, Some(number)) = (is_test, number) {
+            files.push((number, path));
+        }
+    }
+    Ok(files)
 }
 
 #[tauri::command]
@@ -90,22 +437,163 @@ pub fn get_problem(state: State<'_, Mutex<AppState>>) -> Problem {
 pub async fn set_problem(
     problem: Problem,
     state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
 ) -> Result<(), String> {
-    state.lock().unwrap().problem = problem;
+    {
+        let mut state = state.lock().unwrap();
+        state.problem = problem;
+        if state.load_tests()? {
+            handle
+                .emit("set-verdicts", &state.verdicts)
+                .map_to_string()?;
+        }
+    }
     // if state.lock().unwrap().config.toggle.create_file {
     create_file(state).await
     // }
     // Ok(())
 }
 
+#[tauri::command]
+pub fn get_contest(state: State<'_, Mutex<AppState>>) -> Option<Contest> {
+    state.lock().unwrap().contest.clone()
+}
+
+/// Opens another problem of the contest, along with its saved tests.
+#[tauri::command]
+pub fn switch_problem(
+    index: usize,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let state = state.lock().unwrap();
+    let problem = state
+        .contest
+        .as_ref()
+        .and_then(|contest| contest.problems.get(index))
+        .ok_or("problem not found in the contest")?;
+    // Setting the problem loads its tests and opens its file
+    handle.emit("set-problem", problem).map_to_string()
+}
+
 #[tauri::command]
 pub fn get_verdicts(state: State<'_, Mutex<AppState>>) -> Vec<Verdict> {
     state.lock().unwrap().verdicts.clone()
 }
 
 #[tauri::command]
-pub fn set_verdicts(verdicts: Vec<Verdict>, state: State<'_, Mutex<AppState>>) {
-    state.lock().unwrap().verdicts = verdicts
+pub fn set_verdicts(
+    verdicts: Vec<Verdict>,
+    state: State<'_, Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.verdicts = verdicts;
+    // Tests made by the frontend don't have an id yet
+    for verdict in &mut state.verdicts {
+        if verdict.id.is_empty() {
+            verdict.id = Uuid::new_v4().to_string();
+        }
+    }
+    state.save_tests()
+}
+
+#[tauri::command]
+pub fn add_test(
+    input: String,
+    answer: String,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<Verdict, String> {
+    let mut state = state.lock().unwrap();
+    let test = Verdict::new(input, answer);
+    state.verdicts.push(test.clone());
+    state.tests_changed(&handle)?;
+    Ok(test)
+}
+
+/// Changes the input and/or the answer of a test, dropping its last result.
+#[tauri::command]
+pub fn update_test(
+    id: String,
+    input: Option<String>,
+    answer: Option<String>,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    let index = state.get_test_index(&id)?;
+    let test = &mut state.verdicts[index];
+    if let Some(input) = input {
+        test.input = input;
+    }
+    if let Some(answer) = answer {
+        test.answer = answer;
+    }
+    test.reset();
+    state.tests_changed(&handle)
+}
+
+#[tauri::command]
+pub fn delete_test(
+    id: String,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    let index = state.get_test_index(&id)?;
+    state.verdicts.remove(index);
+    state.tests_changed(&handle)
+}
+
+/// Inserts a custom copy of a test right after it.
+#[tauri::command]
+pub fn duplicate_test(
+    id: String,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<Verdict, String> {
+    let mut state = state.lock().unwrap();
+    let index = state.get_test_index(&id)?;
+    let original = &state.verdicts[index];
+    let test = Verdict::new(original.input.clone(), original.answer.clone());
+    state.verdicts.insert(index + 1, test.clone());
+    state.tests_changed(&handle)?;
+    Ok(test)
+}
+
+/// Puts the tests in the order of `ids`, which must list every test exactly once.
+#[tauri::command]
+pub fn reorder_tests(
+    ids: Vec<String>,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    if ids.len() != state.verdicts.len() || ids.iter().collect::<HashSet<_>>().len() != ids.len() {
+        return Err("every test must be listed exactly once".into());
+    }
+
+    let mut reordered = Vec::with_capacity(ids.len());
+    for id in &ids {
+        let index = state.get_test_index(id)?;
+        reordered.push(state.verdicts[index].clone());
+    }
+
+    state.verdicts = reordered;
+    state.tests_changed(&handle)
+}
+
+#[tauri::command]
+pub fn set_test_source(
+    id: String,
+    source: TestSource,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    let index = state.get_test_index(&id)?;
+    state.verdicts[index].source = source;
+    state.tests_changed(&handle)
 }
 
 #[tauri::command]
@@ -113,26 +601,52 @@ pub fn save_state(
     handle: tauri::AppHandle,
     state: State<'_, Mutex<AppState>>,
 ) -> Result<(), String> {
-    let mut file_path = handle.path().app_config_dir().map_to_string()?;
-    file_path.push("cp_state.json");
+    state.lock().unwrap().save(&handle)
+}
 
-    let state = state.lock().unwrap().deref().clone();
+#[tauri::command]
+pub fn get_inbox(state: State<'_, Mutex<AppState>>) -> Vec<InboxEntry> {
+    state.lock().unwrap().inbox.clone()
+}
 
-    let mut f = File::create(file_path).map_to_string()?;
-    f.write_fmt(format_args!(
-        "{}",
-        serde_json::to_string(&state).map_to_string()?
-    ))
-    .map_to_string()?;
+/// Opens a problem from the inbox, replacing the current one.
+#[tauri::command]
+pub fn accept_problem(
+    id: String,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    let index = state
+        .inbox
+        .iter()
+        .position(|entry| entry.id == id)
+        .ok_or("problem not found in the inbox")?;
+    let entry = state.inbox.remove(index);
 
-    Ok(())
+    state.open_problem(entry.problem, entry.verdicts)?;
+    state.save(&handle)?;
+    handle.emit("set-inbox", &state.inbox).map_to_string()?;
+    handle.emit("set-problem", &state.problem).map_to_string()?;
+    handle.emit("set-verdicts", &state.verdicts).map_to_string()
+}
+
+#[tauri::command]
+pub fn dismiss_problem(
+    id: String,
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.inbox.retain(|entry| entry.id != id);
+    state.save(&handle)?;
+    handle.emit("set-inbox", &state.inbox).map_to_string()
 }
 
 #[tauri::command]
 pub async fn copy_code(app_state: State<'_, Mutex<AppState>>) -> Result<(), String> {
     let state = app_state.lock().unwrap();
-    let problem = state.problem.clone();
-    let code = state.config.get_final_code(&problem, &state.directory)?;
+    let code = state.get_final_code()?;
     let mut clipboard = Clipboard::new().map_to_string()?;
     clipboard.set_text(code).map_to_string()?;
     Ok(())
@@ -141,27 +655,9 @@ pub async fn copy_code(app_state: State<'_, Mutex<AppState>>) -> Result<(), Stri
 #[tauri::command]
 pub async fn create_file(app_state: State<'_, Mutex<AppState>>) -> Result<(), String> {
     let state = app_state.lock().unwrap().clone();
-    let config = &state.config;
-
-    let file_path = state
-        .config
-        .get_file_path(&state.problem, &state.directory)?;
-    create_dir_all(&file_path.parent().ok_or("invalid file path".to_string())?).map_to_string()?;
+    let config = &state.get_config();
 
-    if !file_path.exists() {
-        let mut f = File::create_new(&file_path).map_to_string()?;
-        let formatted_time = Local::now().format("%Y/%m/%d %H:%M"); // This is a Display object
-        f.write_fmt(format_args!(
-            "{} Created by {} at {}\n{} {}\n{}",
-            state.get_language()?.comment, // e.g., "//"
-            config.author,                 // e.g., "Ayush Biswas"
-            formatted_time,                // formatted as "2024/12/25 14:07"
-            state.get_language()?.comment, // e.g., "//"
-            state.problem.url,             // problem URL
-            config.get_template(&state.directory)
-        ))
-        .map_to_string()?;
-    }
+    let file_path = state.write_solution_file(&state.problem)?;
 
     if config.toggle.run_on_save {
         let watcher = state.watcher.unwrap();
@@ -170,6 +666,7 @@ pub async fn create_file(app_state: State<'_, Mutex<AppState>>) -> Result<(), St
             .watch(&file_path, RecursiveMode::NonRecursive)
             .map_to_string()?;
     }
+    watch::set_solution(config.toggle.run_on_save.then_some(file_path.as_path()));
 
     let mut cmd = Command::new(config.editor.clone())
         .arg(&file_path)
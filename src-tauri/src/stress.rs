@@ -0,0 +1,329 @@
+use crate::{
+    info::Problem,
+    judge::{Judge, Program, TestSource, Verdict},
+    state::AppState,
+    utils::ResultTrait,
+};
+use regex::Regex;
+use serde::Serialize;
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Mutex,
+};
+use tauri::{AppHandle, Emitter, State};
+use uuid::Uuid;
+
+/// Set by `stop_stress` to end the running stress test or minimization after the
+/// current run.
+static STOP: AtomicBool = AtomicBool::new(false);
+
+/// Cap on the number of solution runs while minimizing a single test.
+const MINIMIZE_RUNS: usize = 1000;
+
+#[derive(Serialize, Clone)]
+pub struct StressProgress {
+    pub iteration: usize,
+    pub seed: u64,
+}
+
+#[derive(Serialize, Clone)]
+pub struct MinimizeProgress {
+    pub runs: usize,
+    pub size: usize,
+}
+
+#[tauri::command]
+pub fn set_generator(
+    generator: Option<Program>,
+    state: State<'_, Mutex<AppState>>,
+) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.problem.generator = generator;
+    state.save_tests()
+}
+
+#[tauri::command]
+pub fn set_brute(brute: Option<Program>, state: State<'_, Mutex<AppState>>) -> Result<(), String> {
+    let mut state = state.lock().unwrap();
+    state.problem.brute = brute;
+    state.save_tests()
+}
+
+/// Runs the solution against the brute force solution on inputs made by the generator,
+/// which gets the seed as its only argument. The first input on which they disagree is
+/// added to the tests and its verdict returned.
+#[tauri::command]
+pub async fn stress(
+    iterations: usize,
+    seed: Option<u64>,
+    app_state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<Option<Verdict>, String> {
+    STOP.store(false, Ordering::Relaxed);
+
+    // Stress tests run for a while, so don't hold the lock meanwhile
+    let state = app_state.lock().unwrap().clone();
+    if state.problem.interactive {
+        return Err("Stress testing interactive problems is not supported".into());
+    }
+
+    let generator = state
+        .problem
+        .generator
+        .as_ref()
+        .ok_or("No generator set for this problem")?
+        .build(&state, "generator")?;
+    let brute = state
+        .problem
+        .brute
+        .as_ref()
+        .ok_or("No brute force solution set for this problem")?
+        .build(&state, "brute force solution")?;
+
+    let (solution, source_map) = state.build_solution()?;
+    let judge = Judge::new(&state, solution, source_map)?;
+    judge
+        .solution
+        .compile()
+        .map_err(|e| format!("Compilation Error\n{}", judge.map_diagnostics(&e)))?;
+
+    let seed = seed.unwrap_or_else(|| Uuid::new_v4().as_u128() as u32 as u64);
+    for iteration in 0..iterations {
+        if STOP.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let seed = seed + iteration as u64;
+        handle
+            .emit("stress-progress", StressProgress { iteration, seed })
+            .map_to_string()?;
+
+        let input = judge
+            .execute(&generator, &[seed.to_string()], "")
+            .map_err(|e| format!("Generator failed on seed {seed}\n{e}"))?;
+        let answer = judge
+            .execute(&brute, &[], &input)
+            .map_err(|e| format!("Brute force solution failed on seed {seed}\n{e}"))?;
+
+        let verdict = judge.run(Verdict::new(input, answer))?;
+        if verdict.status_id != 3 {
+            add_test(&app_state, &handle, &state.problem, &verdict)?;
+            return Ok(Some(verdict));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Shrinks the input of a failing test with delta debugging over its lines and then its
+/// tokens, keeping every step that still fails. With a brute force solution a step fails
+/// when the solution disagrees with it. Otherwise only runtime errors can be minimized, and
+/// a step fails when the first line of the error output stays the same. The minimized test
+/// is added to the tests.
+#[tauri::command]
+pub async fn minimize(
+    index: usize,
+    app_state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Result<Verdict, String> {
+    STOP.store(false, Ordering::Relaxed);
+
+    let state = app_state.lock().unwrap().clone();
+    if state.problem.interactive {
+        return Err("Minimizing tests of interactive problems is not supported".into());
+    }
+    let test = state.verdicts.get(index).ok_or("test not found")?.clone();
+
+    let brute = match &state.problem.brute {
+        Some(brute) => Some(brute.build(&state, "brute force solution")?),
+        None => None,
+    };
+
+    let (solution, source_map) = state.build_solution()?;
+    let judge = Judge::new(&state, solution, source_map)?;
+    judge
+        .solution
+        .compile()
+        .map_err(|e| format!("Compilation Error\n{}", judge.map_diagnostics(&e)))?;
+
+    let original = judge.run(Verdict::new(test.input.clone(), test.answer.clone()))?;
+    let crash = first_line(&original.stderr);
+    match original.status_id {
+        3 => return Err("The test passes, there is nothing to minimize".into()),
+        _ if brute.is_some() => {}
+        // Without the answers of the brute force solution, almost any input cut short fails
+        // the same way, so only a crash that reports where it happened can be told apart
+        11 if !crash.is_empty() => {}
+        _ => {
+            return Err(format!(
+                "Minimizing a test with {} needs a brute force solution",
+                original.status
+            ))
+        }
+    }
+
+    let mut smallest = original.clone();
+    let mut runs = 0;
+    let mut fails = |input: &str| -> Result<bool, String> {
+        runs += 1;
+        let answer = match &brute {
+            Some(brute) => match judge.execute(brute, &[], input) {
+                Ok(answer) => answer,
+                // An input the brute force solution can't handle is not a valid test
+                Err(_) => return Ok(false),
+            },
+            None => String::new(),
+        };
+
+        let verdict = judge.run(Verdict::new(input.into(), answer))?;
+        let failed = match brute {
+            Some(_) => verdict.status_id != 3,
+            None => verdict.status_id == original.status_id && first_line(&verdict.stderr) == crash,
+        };
+        if failed {
+            handle
+                .emit(
+                    "minimize-progress",
+                    MinimizeProgress {
+                        runs,
+                        size: input.len(),
+                    },
+                )
+                .map_to_string()?;
+            smallest = verdict;
+        }
+        Ok(failed)
+    };
+
+    let mut budget = MINIMIZE_RUNS;
+    let lines = test.input.split_inclusive('\n').map(String::from).collect();
+    let input = ddmin(lines, &mut fails, &mut budget)?.concat();
+    let tokens = Regex::new(r"\S+\s*")
+        .unwrap()
+        .find_iter(&input)
+        .map(|m| m.as_str().to_string())
+        .collect();
+    ddmin(tokens, &mut fails, &mut budget)?;
+
+    if brute.is_none() && smallest.input != test.input {
+        // The original answer doesn't belong to the smaller input
+        smallest.answer = String::new();
+    }
+
+    add_test(&app_state, &handle, &state.problem, &smallest)?;
+    Ok(smallest)
+}
+
+/// Adds a generated test to the problem it was made for, which may no longer be the current
+/// one once a long run is over.
+fn add_test(
+    app_state: &Mutex<AppState>,
+    handle: &AppHandle,
+    problem: &Problem,
+    test: &Verdict,
+) -> Result<(), String> {
+    let mut state = app_state.lock().unwrap();
+    let verdict = Verdict {
+        source: TestSource::Generated,
+        ..Verdict::new(test.input.clone(), test.answer.clone())
+    };
+    if state.problem.url == problem.url {
+        state.verdicts.push(verdict);
+        state.save_tests()?;
+        return handle.emit("set-verdicts", &state.verdicts).map_to_string();
+    }
+
+    let (problem, mut verdicts) = state
+        .read_tests(problem)?
+        .unwrap_or_else(|| (problem.clone(), vec![]));
+    verdicts.push(verdict);
+    state.save_tests_of(&problem, &verdicts)
+}
+
+/// First line of the error output of a run, which tells where a crash happened.
+fn first_line(stderr: &str) -> String {
+    stderr.lines().next().unwrap_or_default().trim().to_string()
+}
+
+/// Delta debugging: repeatedly drops a chunk of `units` while `fails` still holds for the
+/// rest, splitting into smaller chunks whenever none of them can be dropped. Every call to
+/// `fails` uses up one unit of `budget`.
+fn ddmin(
+    mut units: Vec<String>,
+    fails: &mut impl FnMut(&str) -> Result<bool, String>,
+    budget: &mut usize,
+) -> Result<Vec<String>, String> {
+    let mut n = 2;
+    while units.len() >= 2 {
+        let chunk = units.len().div_ceil(n);
+        let mut reduced = false;
+
+        for start in (0..units.len()).step_by(chunk) {
+            if *budget == 0 || STOP.load(Ordering::Relaxed) {
+                return Ok(units);
+            }
+            *budget -= 1;
+
+            let end = (start + chunk).min(units.len());
+            let rest = [&units[..start], &units[end..]].concat();
+            if fails(&rest.concat())? {
+                units = rest;
+                n = (n - 1).max(2);
+                reduced = true;
+                break;
+            }
+        }
+
+        if !reduced {
+            if n >= units.len() {
+                break;
+            }
+            n = (2 * n).min(units.len());
+        }
+    }
+    Ok(units)
+}
+
+#[tauri::command]
+pub fn stop_stress() {
+    STOP.store(true, Ordering::Relaxed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn units(n: usize) -> Vec<String> {
+        (1..=n).map(|i| format!("{i}\n")).collect()
+    }
+
+    #[test]
+    fn ddmin_keeps_what_fails() {
+        let mut fails = |input: &str| Ok(input.contains("3\n") && input.contains("6\n"));
+        let mut budget = 100;
+        let units = ddmin(units(8), &mut fails, &mut budget).unwrap();
+        assert_eq!(units, ["3\n", "6\n"]);
+        assert!(budget > 0);
+    }
+
+    #[test]
+    fn ddmin_stops_at_the_budget() {
+        let mut runs = 0;
+        let mut fails = |input: &str| {
+            runs += 1;
+            Ok(input.contains("5\n"))
+        };
+        let mut budget = 2;
+        let units = ddmin(units(8), &mut fails, &mut budget).unwrap();
+        assert_eq!(budget, 0);
+        assert_eq!(runs, 2);
+        assert!(units.contains(&"5\n".to_string()));
+    }
+
+    #[test]
+    fn ddmin_passes_on_errors() {
+        let mut fails = |_: &str| Err("solution failed".to_string());
+        let mut budget = 100;
+        assert!(ddmin(units(4), &mut fails, &mut budget).is_err());
+    }
+}
@@ -30,8 +30,7 @@ pub struct EmptySolution {
 pub async fn get_submit(data: web::Data<WebState>) -> impl Responder {
     let sol = data.sol.lock().unwrap().take();
 
-    if sol.is_some() {
-        let solution = sol.unwrap();
+    if let Some(solution) = sol {
 
         #[cfg(debug_assertions)]
         println!("submitting solution");
@@ -58,9 +57,7 @@ pub async fn post_submit(sol: web::Json<Solution>, data: web::Data<WebState>) ->
 #[tauri::command]
 pub async fn submit_solution(app_state: tauri::State<'_, Mutex<AppState>>) -> Result<(), String> {
     let state = app_state.lock().unwrap().clone();
-    let source_code = state
-        .config
-        .get_final_code(&state.problem, &state.directory)?;
+    let source_code = state.get_final_code()?;
     let client = reqwest::Client::builder().build().map_to_string()?;
 
     let problem_name = state
@@ -31,28 +31,44 @@ pub fn resolve_path(dir: &Path, path: &str) -> PathBuf {
 }
 
 pub fn extract_code_block(source: &str) -> String {
+    extract_code_block_with_line(source).0
+}
+
+/// The code between the `@code begin` and `@code end` markers, or the whole source without
+/// them, and the line of the source the code starts at.
+pub fn extract_code_block_with_line(source: &str) -> (String, usize) {
     if !source.contains("@code") {
-        return source.into()
+        return (source.into(), 0)
     }
 
-    let mut lines = source.lines();
+    let mut lines = source.lines().enumerate();
+    let mut start = 0;
     // Find the start marker
-    while let Some(line) = lines.next() {
+    for (i, line) in lines.by_ref() {
         if line.contains("@code") && line.contains("begin") {
+            start = i + 1;
             break;
         }
     }
     // Collect lines until the end marker
     let mut code_block = Vec::new();
-    for line in lines {
+    for (_, line) in lines {
         if line.contains("@code") && line.contains("end") {
             break;
         }
         code_block.push(line);
     }
     if code_block.is_empty() {
-        source.to_string()
+        (source.to_string(), 0)
     } else {
-        code_block.join("\n").trim().to_string()
+        // Only whole blank lines are trimmed, to keep the indentation of the first one
+        while code_block.last().is_some_and(|line| line.trim().is_empty()) {
+            code_block.pop();
+        }
+        let skipped = code_block
+            .iter()
+            .take_while(|line| line.trim().is_empty())
+            .count();
+        (code_block[skipped..].join("\n"), start + skipped)
     }
 }
@@ -0,0 +1,310 @@
+use crate::{
+    config::{get_config_paths, Config, LayeredConfig, ProblemContext},
+    info::Problem,
+    state::AppState,
+    utils::resolve_path,
+};
+use handlebars::{no_escape, Handlebars};
+use handlebars_misc_helpers::register;
+use regex::Regex;
+use serde::Serialize;
+use serde_json::json;
+use std::{
+    collections::{BTreeMap, HashMap},
+    env,
+    fmt::Display,
+    path::Path,
+    sync::Mutex,
+};
+use tauri::State;
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// Something will fail when it is used
+    Error,
+    /// Something works, but likely not as intended
+    Warning,
+}
+
+#[derive(Serialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum DiagnosticKind {
+    /// A config file isn't valid TOML or has values of the wrong type
+    Parse,
+    /// A Handlebars template doesn't compile or render
+    Template,
+    /// `lib_check_regex` doesn't make a valid regex
+    Regex,
+    /// The filename template renders to nothing usable
+    Filename,
+    /// A file the config points at doesn't exist
+    Path,
+    /// The editor can't be found
+    Editor,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub kind: DiagnosticKind,
+    /// Dotted key of the value, e.g. "code.filename", empty for a whole file
+    pub key: String,
+    /// File the value came from, or "default"
+    pub file: String,
+    /// Starting from 1, when the diagnostic points at a place in the file
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+    pub message: String,
+}
+
+/// Checks the config of the current problem and language without using it.
+#[tauri::command]
+pub fn validate_config(
+    state: State<'_, Mutex<AppState>>,
+    handle: tauri::AppHandle,
+) -> Vec<Diagnostic> {
+    let state = state.lock().unwrap();
+    validate(&state, &handle)
+}
+
+pub fn validate(state: &AppState, handle: &tauri::AppHandle) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+
+    let mut layers = LayeredConfig::default();
+    let paths = get_config_paths(state, handle)
+        .into_iter()
+        .chain(state.get_override_paths(&state.problem));
+    for path in paths {
+        if let Err(e) = layers.merge_file(&path) {
+            diagnostics.push(Diagnostic {
+                severity: Severity::Error,
+                kind: DiagnosticKind::Parse,
+                key: String::new(),
+                file: e.file,
+                line: e.line,
+                column: e.column,
+                message: e.message,
+            });
+        }
+    }
+    // Files that don't fit are left out, so the rest is always valid
+    let Ok(config) = layers.get_config() else {
+        return diagnostics;
+    };
+
+    let mut validator = Validator {
+        sources: layers.get_sources(),
+        dir: &state.directory,
+        context: sample_context(state, &config),
+        diagnostics,
+    };
+    validator.check_config(&config);
+    validator.diagnostics
+}
+
+/// What the templates are rendered with, since there may be no problem yet.
+fn sample_context(state: &AppState, config: &Config) -> ProblemContext {
+    let problem = Problem {
+        title: "A. Sample Problem".into(),
+        url: "https://codeforces.com/problemset/problem/1/A".into(),
+        group: "Codeforces - Codeforces Round 1".into(),
+        time_limit: 1000,
+        memory_limit: 256,
+        ..Default::default()
+    };
+    ProblemContext::new(&problem, state.get_language().ok().as_ref(), &config.author)
+}
+
+struct Validator<'a> {
+    sources: &'a BTreeMap<String, String>,
+    dir: &'a Path,
+    context: ProblemContext,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Validator<'_> {
+    fn push(&mut self, severity: Severity, kind: DiagnosticKind, key: &str, message: impl Display) {
+        self.diagnostics.push(Diagnostic {
+            severity,
+            kind,
+            key: key.into(),
+            file: self.sources.get(key).cloned().unwrap_or_default(),
+            line: None,
+            column: None,
+            message: message.to_string(),
+        });
+    }
+
+    fn check_config(&mut self, config: &Config) {
+        let code = &config.code;
+        let fields = [
+            ("filename", &code.filename),
+            ("template", &code.template),
+            ("modifier", &code.modifier),
+            ("lib_check_regex", &code.lib_check_regex),
+            ("header", &code.header),
+        ];
+        for (field, value) in fields {
+            self.check_code_field(&format!("code.{field}"), field, value);
+        }
+        self.check_include("include", &config.include);
+
+        for (name, profile) in &config.profiles {
+            let prefix = format!("profiles.{name}");
+            let fields = [
+                ("filename", &profile.filename),
+                ("template", &profile.template),
+                ("modifier", &profile.modifier),
+                ("lib_check_regex", &profile.lib_check_regex),
+                ("header", &profile.header),
+            ];
+            for (field, value) in fields {
+                if let Some(value) = value {
+                    self.check_code_field(&format!("{prefix}.{field}"), field, value);
+                }
+            }
+            if let Some(include) = &profile.include {
+                self.check_include(&format!("{prefix}.include"), include);
+            }
+        }
+
+        if !find_program(&config.editor) {
+            self.push(
+                Severity::Error,
+                DiagnosticKind::Editor,
+                "editor",
+                format!("{} was not found", config.editor),
+            );
+        }
+    }
+
+    fn check_code_field(&mut self, key: &str, field: &str, value: &str) {
+        match field {
+            "filename" => {
+                let Some(name) = self.render(key, value) else {
+                    return;
+                };
+                if name.trim().is_empty() {
+                    self.push(
+                        Severity::Warning,
+                        DiagnosticKind::Filename,
+                        key,
+                        "renders to an empty filename for a sample problem",
+                    );
+                }
+            }
+            "template" => {
+                if value.is_empty() {
+                    return;
+                }
+                let path = resolve_path(self.dir, value);
+                let Ok(template) = std::fs::read_to_string(&path) else {
+                    self.push(
+                        Severity::Warning,
+                        DiagnosticKind::Path,
+                        key,
+                        format!("{path:?} can't be read, new files start empty"),
+                    );
+                    return;
+                };
+                if path.extension().is_none_or(|ext| ext != "hbs") {
+                    return;
+                }
+                if let Err(e) = renderer().render_template(&template, &self.context) {
+                    self.push(
+                        Severity::Error,
+                        DiagnosticKind::Template,
+                        key,
+                        format!("{e}, new files can't be created"),
+                    );
+                }
+            }
+            "lib_check_regex" => {
+                if !self.compile(key, value) {
+                    return;
+                }
+                let pattern = match renderer().render_template(value, &json!({"name": "lib"})) {
+                    Ok(pattern) => pattern,
+                    Err(e) => {
+                        self.push(Severity::Error, DiagnosticKind::Template, key, e);
+                        return;
+                    }
+                };
+                if let Err(e) = Regex::new(&pattern) {
+                    self.push(Severity::Error, DiagnosticKind::Regex, key, e);
+                }
+            }
+            _ => {
+                self.compile(key, value);
+            }
+        }
+    }
+
+    fn check_include(&mut self, prefix: &str, include: &HashMap<String, String>) {
+        for (name, value) in include {
+            let path = resolve_path(self.dir, value);
+            if !path.exists() {
+                self.push(
+                    Severity::Error,
+                    DiagnosticKind::Path,
+                    &format!("{prefix}.{name}"),
+                    format!("{path:?} doesn't exist"),
+                );
+            }
+        }
+    }
+
+    /// Whether the template compiles, reporting it when it doesn't.
+    fn compile(&mut self, key: &str, template: &str) -> bool {
+        match renderer().register_template_string(key, template) {
+            Ok(()) => true,
+            Err(e) => {
+                self.push(Severity::Error, DiagnosticKind::Template, key, e);
+                false
+            }
+        }
+    }
+
+    /// Renders the template for the sample problem, reporting when it fails.
+    fn render(&mut self, key: &str, template: &str) -> Option<String> {
+        if !self.compile(key, template) {
+            return None;
+        }
+        match renderer().render_template(template, &self.context) {
+            Ok(rendered) => Some(rendered),
+            Err(e) => {
+                self.push(Severity::Error, DiagnosticKind::Template, key, e);
+                None
+            }
+        }
+    }
+}
+
+fn renderer() -> Handlebars<'static> {
+    let mut bars = Handlebars::new();
+    register(&mut bars);
+    bars.register_escape_fn(no_escape);
+    bars
+}
+
+/// Whether the program is a file, or can be found in `PATH` like a shell would.
+fn find_program(program: &str) -> bool {
+    let path = Path::new(program);
+    if path.components().count() > 1 {
+        return path.is_file();
+    }
+
+    let suffixes: &[&str] = if cfg!(windows) {
+        &["", ".exe", ".cmd", ".bat"]
+    } else {
+        &[""]
+    };
+    env::var_os("PATH").is_some_and(|paths| {
+        env::split_paths(&paths).any(|dir| {
+            suffixes
+                .iter()
+                .any(|suffix| dir.join(format!("{program}{suffix}")).is_file())
+        })
+    })
+}
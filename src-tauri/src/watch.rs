@@ -0,0 +1,152 @@
+use crate::{
+    config::{load_config, Config, ConfigError},
+    language::{list_languages, read_languages, Language},
+    state::AppState,
+    WINDOW,
+};
+use notify::{Event, EventKind};
+use serde::Serialize;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{mpsc::Receiver, Mutex},
+    time::{Duration, Instant},
+};
+use tauri::{Emitter, Manager};
+
+/// Editors often write a file more than once when saving it.
+const DEBOUNCE: Duration = Duration::from_secs(1);
+
+/// Sent to the frontend after a config file changed on disk.
+#[derive(Serialize, Clone)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Reload {
+    Config { config: Box<Config> },
+    Languages { languages: Vec<Language> },
+    Error { error: ConfigError },
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+enum Watched {
+    Solution,
+    Config,
+    Languages,
+}
+
+/// The files whose changes are acted on, kept apart from the state so that events don't wait
+/// on a running test holding it. The paths are canonical.
+struct WatchedPaths {
+    solution: Option<PathBuf>,
+    config: Vec<PathBuf>,
+    languages: Option<PathBuf>,
+}
+
+static WATCHED: Mutex<WatchedPaths> = Mutex::new(WatchedPaths {
+    solution: None,
+    config: vec![],
+    languages: None,
+});
+
+/// Sets the solution file whose saves run the tests, None to run them on no file.
+pub fn set_solution(path: Option<&Path>) {
+    WATCHED.lock().unwrap().solution = path.map(canonical);
+}
+
+/// Sets the config files that are reloaded when saved.
+pub fn set_config(paths: &[PathBuf]) {
+    WATCHED.lock().unwrap().config = paths.iter().map(|p| canonical(p)).collect();
+}
+
+/// Sets the languages file that is reloaded when saved.
+pub fn set_languages(path: &Path) {
+    WATCHED.lock().unwrap().languages = Some(canonical(path));
+}
+
+/// The path with its directory resolved, so that it can be compared with the paths of events,
+/// which may go through symlinks like `/var` to `/private/var` on macOS. The file itself may
+/// not exist yet.
+fn canonical(path: &Path) -> PathBuf {
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => dir
+            .canonicalize()
+            .map_or_else(|_| path.to_path_buf(), |dir| dir.join(name)),
+        _ => path.to_path_buf(),
+    }
+}
+
+/// Handles the events of the watcher until it is dropped: saving the solution runs the tests,
+/// and saving a config file reloads it.
+pub fn handle_events(rx: Receiver<notify::Result<Event>>) {
+    let window = WINDOW.get().expect("window-is-unavailable");
+    let handle = window.app_handle();
+    let mut previous = HashMap::new();
+
+    for res in rx {
+        let event = match res {
+            Ok(event) => event,
+            Err(e) => {
+                println!("watch error: {:?}", e);
+                continue;
+            }
+        };
+        // Reading the files makes events too
+        if !matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) {
+            continue;
+        }
+        let Some(watched) = get_watched(&event.paths) else {
+            continue;
+        };
+
+        let now = Instant::now();
+        if previous
+            .get(&watched)
+            .is_some_and(|time| now.duration_since(*time) < DEBOUNCE)
+        {
+            continue;
+        }
+        previous.insert(watched, now);
+
+        let _ = match watched {
+            Watched::Solution => window.emit("test", 0),
+            Watched::Config => window.emit("config-reloaded", reload_config(handle)),
+            Watched::Languages => window.emit("config-reloaded", reload_languages(handle)),
+        };
+    }
+}
+
+fn get_watched(paths: &[PathBuf]) -> Option<Watched> {
+    let watched = WATCHED.lock().unwrap();
+    paths.iter().map(|path| canonical(path)).find_map(|path| {
+        if Some(&path) == watched.solution.as_ref() {
+            Some(Watched::Solution)
+        } else if watched.config.contains(&path) {
+            Some(Watched::Config)
+        } else if Some(&path) == watched.languages.as_ref() {
+            Some(Watched::Languages)
+        } else {
+            None
+        }
+    })
+}
+
+fn reload_config(handle: &tauri::AppHandle) -> Reload {
+    let state = handle.state::<Mutex<AppState>>();
+    let mut state = state.lock().unwrap();
+    match load_config(&mut state, handle) {
+        Ok(()) => Reload::Config {
+            config: Box::new(state.config.clone()),
+        },
+        Err(error) => Reload::Error { error },
+    }
+}
+
+fn reload_languages(handle: &tauri::AppHandle) -> Reload {
+    match read_languages(handle) {
+        Ok(languages) => {
+            let list = list_languages(&languages);
+            handle.state::<Mutex<AppState>>().lock().unwrap().languages = languages;
+            Reload::Languages { languages: list }
+        }
+        Err(error) => Reload::Error { error },
+    }
+}
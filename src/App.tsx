@@ -4,10 +4,13 @@ import TitleBar from "./Titlebar.tsx";
 import LandingPage from "./LandingPage.tsx";
 import {useEffect, useState} from "react";
 import Home from "./Home.tsx";
-import {Problem, Verdict} from "./Languages.ts";
+import {Contest, InboxEntry, Problem, Reload, Summary, TestResult, Verdict} from "./Languages.ts";
 import {listen} from "@tauri-apps/api/event";
+import {notifications} from "@mantine/notifications";
 import {
+    get_contest,
     get_directory,
+    get_inbox,
     get_problem,
     get_verdicts,
     set_problem,
@@ -20,12 +23,16 @@ function App() {
     const [directory, setDirectory] = useState("");
     const [problem, setProblem] = useState<Problem | null>(null);
     const [verdicts, setVerdicts] = useState<Verdict[]>([]);
+    const [contest, setContest] = useState<Contest | null>(null);
+    const [inbox, setInbox] = useState<InboxEntry[]>([]);
     const [loading, setLoading] = useState(false);
 
     useEffect(() => {
         get_directory().then((dir) => setDirectory(dir));
         get_problem().then((pro) => setProblem(pro));
         get_verdicts().then((ver) => setVerdicts(ver || []));
+        get_contest().then((con) => setContest(con));
+        get_inbox().then((inb) => setInbox(inb));
         listen<number>("test", async (event) => {
             if (!loading) {
                 setLoading(true);
@@ -39,9 +46,53 @@ function App() {
         listen<Problem>("set-problem", (event) =>
             set_problem(event.payload).then(() => setProblem(event.payload)),
         );
+        listen<Contest>("set-contest", (event) => setContest(event.payload));
+        listen<InboxEntry[]>("set-inbox", (event) => setInbox(event.payload));
         listen<Verdict[]>("set-verdicts", (event) =>
             set_verdicts(event.payload).then(() => setVerdicts(event.payload)),
         );
+        listen<TestResult>("set-verdict", (event) =>
+            setVerdicts((verdicts) =>
+                verdicts.map((v) => v.id === event.payload.id ? event.payload.verdict : v),
+            ),
+        );
+        listen<Reload>("config-reloaded", (event) => {
+            if (event.payload.kind === "error") {
+                const {file, line, column, message} = event.payload.error;
+                const position = line !== null ? `:${line}:${column}` : "";
+                notifications.show({
+                    id: "config-reloaded",
+                    message: `${file}${position}: ${message}`,
+                    color: "red",
+                });
+            } else {
+                notifications.show({
+                    id: "config-reloaded",
+                    message: event.payload.kind === "config" ? "Config reloaded" : "Languages reloaded",
+                    color: "teal",
+                });
+            }
+        });
+        listen<string>("error", (event) =>
+            notifications.show({
+                message: event.payload,
+                color: "red",
+            }),
+        );
+        listen<string>("warning", (event) =>
+            notifications.show({
+                message: event.payload,
+                color: "yellow",
+            }),
+        );
+        listen<Summary>("test-summary", (event) => {
+            const {passed, total, max_time, max_memory} = event.payload;
+            notifications.show({
+                id: "test-summary",
+                message: `${passed}/${total} passed, ${Math.round(max_time)} ms, ${Math.floor(max_memory / 1024)} Mb`,
+                color: passed === total ? "teal" : "red",
+            });
+        });
     }, []);
 
     return (
@@ -54,7 +105,7 @@ function App() {
         >
             <TitleBar setDirectory={setDirectory} directory={directory} loading={loading} setLoading={setLoading}/>
             {directory === "" && <LandingPage setDirectory={setDirectory}/>}
-            {directory !== "" && <Home problem={problem} verdicts={verdicts}/>}
+            {directory !== "" && <Home problem={problem} verdicts={verdicts} contest={contest} inbox={inbox}/>}
         </Box>
     );
 }
@@ -2,9 +2,9 @@ import {Box, Center, Flex, Group, Select, Stack, Text, Textarea,} from "@mantine
 import {useEffect, useState} from "react";
 import {Effect, LogicalSize} from "@tauri-apps/api/window";
 import {getCurrentWebviewWindow} from "@tauri-apps/api/webviewWindow";
-import {Problem, Verdict} from "./Languages.ts";
-import {IconPlus} from "@tabler/icons-react";
-import {emit} from "@tauri-apps/api/event";
+import {Contest, InboxEntry, Problem, Verdict} from "./Languages.ts";
+import {IconPlus, IconX} from "@tabler/icons-react";
+import {accept_problem, add_test, dismiss_problem, switch_problem} from "./commands.tsx";
 
 const appWindow = getCurrentWebviewWindow();
 
@@ -49,9 +49,13 @@ const MyTextarea = ({
 const Home = ({
   problem,
   verdicts,
+  contest,
+  inbox,
 }: {
   problem: Problem | null;
   verdicts: Verdict[];
+  contest: Contest | null;
+  inbox: InboxEntry[];
 }) => {
   let [caseIndex, setCaseIndex] = useState(0);
   const [input, setInput] = useState("");
@@ -94,19 +98,9 @@ const Home = ({
     return setAny;
   };
 
-  const onAddTestCase = () => {
+  const onAddTestCase = async () => {
     if (!editable) return;
-    verdicts.push({
-      input: input,
-      answer: answer,
-      output: "",
-      stderr: "",
-      memory: 0,
-      status: "NA",
-      status_id: 0,
-      time: 0,
-    });
-    emit("set-verdicts", verdicts);
+    await add_test(input, answer);
   };
 
   useEffect(() => {
@@ -118,9 +112,56 @@ const Home = ({
     appWindow.setEffects({effects: [Effect.Blur]}).then(null);
   }, [verdicts]);
 
+  const inboxBar = inbox.length > 0 && (
+    <Group mt={5} mx={"auto"} gap={"xs"}>
+      {inbox.map((entry) => (
+        <Group
+          key={entry.id}
+          gap={4}
+          px={8}
+          bg={"#2b2d30"}
+          c={"white"}
+          className={"rounded-md"}
+          title={`Received at ${entry.received}`}
+        >
+          <Text
+            fz={14}
+            className={"cursor-pointer"}
+            onClick={() => accept_problem(entry.id)}
+          >
+            {entry.problem.title}
+          </Text>
+          <IconX
+            size={14}
+            className={"cursor-pointer"}
+            onClick={() => dismiss_problem(entry.id)}
+          />
+        </Group>
+      ))}
+    </Group>
+  );
+
   return problem?.title ? (
     <Stack h={"95%"}>
+      {inboxBar}
       <Flex className={"mt-5 justify-center content-center"}>
+        {contest && contest.problems.length > 1 && (
+          <Select
+            variant="unstyled"
+            data={contest.problems.map((p, i) => ({
+              label: p.title,
+              value: `${i}`,
+            }))}
+            value={`${contest.problems.findIndex((p) => p.url === problem?.url)}`}
+            onChange={(v) => v !== null && switch_problem(parseInt(v))}
+            w={200}
+            pl={10}
+            my={"auto"}
+            bg={"#2b2d30"}
+            className={"rounded-md"}
+            allowDeselect={false}
+          />
+        )}
         <Text
           w={500}
           fz={32}
@@ -218,11 +259,14 @@ const Home = ({
       </Stack>
     </Stack>
   ) : (
-    <Center h={"90%"}>
-      <Text c={"#acacac"} fz={32} className={"tracking-wider"}>
-        Select problem from competitive companion
-      </Text>
-    </Center>
+    <Stack h={"90%"}>
+      {inboxBar}
+      <Center h={"100%"}>
+        <Text c={"#acacac"} fz={32} className={"tracking-wider"}>
+          Select problem from competitive companion
+        </Text>
+      </Center>
+    </Stack>
   );
 };
 
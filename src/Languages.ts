@@ -12,11 +12,110 @@ export type Verdict = {
   status_id: number;
   time: number;
   memory: number;
+  message?: string;
+  id?: string;
+  source?: TestSource;
 };
 
+export type TestSource = "sample" | "custom" | "generated";
+
+export type TestResult = {
+  index: number;
+  id: string;
+  verdict: Verdict;
+};
+
+export type Summary = {
+  passed: number;
+  total: number;
+  max_time: number;
+  max_memory: number;
+};
+
+export type Program = {
+  file: string;
+  language_id: number;
+};
+
+export type Checker = Program & {
+  testlib?: boolean;
+};
+
+export type Comparator =
+  | "exact"
+  | "lines"
+  | "tokens"
+  | "tokens_ignore_case"
+  | "float";
+
 export type Problem = {
   url: string;
   memory_limit: number;
   time_limit: number;
   title: string;
+  interactive?: boolean;
+  interactor?: Program | null;
+  checker?: Checker | null;
+  comparator?: Comparator | null;
+  epsilon?: number | null;
+  generator?: Program | null;
+  brute?: Program | null;
+  group?: string;
+  test_type?: "single" | "multiNumber";
+  input?: { type: "stdin" } | { type: "file"; fileName: string } | { type: "regex"; pattern: string };
+  output?: { type: "stdout" } | { type: "file"; fileName: string };
+  languages?: { java?: { mainClass: string; taskClass: string } | null };
+  received?: number;
+};
+
+export type InboxEntry = {
+  id: string;
+  problem: Problem;
+  verdicts: Verdict[];
+  received: string;
+};
+
+export type ConfigError = {
+  file: string;
+  line: number | null;
+  column: number | null;
+  message: string;
+};
+
+export type Reload =
+  | { kind: "config"; config: Record<string, any> }
+  | { kind: "languages"; languages: Language[] }
+  | { kind: "error"; error: ConfigError };
+
+export type Diagnostic = {
+  severity: "error" | "warning";
+  kind: "parse" | "template" | "regex" | "filename" | "path" | "editor";
+  key: string;
+  file: string;
+  line: number | null;
+  column: number | null;
+  message: string;
 };
+
+export type EffectiveConfig = {
+  config: Record<string, any>;
+  sources: Record<string, string>;
+};
+
+export type Contest = {
+  id: string;
+  size: number;
+  problems: Problem[];
+};
+
+export type MinimizeProgress = {
+  runs: number;
+  size: number;
+};
+
+export type StressProgress = {
+  iteration: number;
+  seed: number;
+};
+
+export type ConfigScope = "global" | "workspace";
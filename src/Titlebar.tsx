@@ -14,6 +14,8 @@ import {
   submit,
 } from "./commands.tsx";
 import { IconCheck } from "@tabler/icons-react";
+import { listen } from "@tauri-apps/api/event";
+import { Reload } from "./Languages.ts";
 
 const appWindow = getCurrentWindow();
 
@@ -70,6 +72,15 @@ const TitleBar = ({
     );
 
     read_config();
+    listen<Reload>("config-reloaded", (event) => {
+      if (event.payload.kind === "languages") {
+        setLanguages(
+          event.payload.languages.map((x) => {
+            return { value: x.id.toString(), label: x.name };
+          }),
+        );
+      }
+    });
     window.addEventListener("focus", () => setIsFocused(true));
     window.addEventListener("blur", () => setIsFocused(false));
   }, []);
@@ -1,7 +1,20 @@
 import { invoke } from "@tauri-apps/api/core";
 import { notifications } from "@mantine/notifications";
 import { IconCheck, IconX } from "@tabler/icons-react";
-import { Language, Problem, Verdict } from "./Languages.ts";
+import {
+  Checker,
+  Comparator,
+  ConfigScope,
+  Contest,
+  Diagnostic,
+  EffectiveConfig,
+  InboxEntry,
+  Language,
+  Problem,
+  Program,
+  TestSource,
+  Verdict,
+} from "./Languages.ts";
 
 async function invokeWithNotify<T>(
   cmd: string,
@@ -88,6 +101,46 @@ export const get_problem = async () =>
     "Could not get problem",
   );
 
+export const get_contest = async () =>
+  await invokeWithNotify<Contest | null>(
+    "get_contest",
+    {},
+    undefined,
+    "Could not get contest",
+  );
+
+export const switch_problem = async (index: number) =>
+  (await invokeWithNotify<null>(
+    "switch_problem",
+    { index },
+    undefined,
+    "Could not switch problem",
+  )) ?? false;
+
+export const get_inbox = async () =>
+  (await invokeWithNotify<InboxEntry[]>(
+    "get_inbox",
+    {},
+    undefined,
+    "Could not get the inbox",
+  )) ?? [];
+
+export const accept_problem = async (id: string) =>
+  (await invokeWithNotify<null>(
+    "accept_problem",
+    { id },
+    undefined,
+    "Could not open problem",
+  )) ?? false;
+
+export const dismiss_problem = async (id: string) =>
+  (await invokeWithNotify<null>(
+    "dismiss_problem",
+    { id },
+    undefined,
+    "Could not dismiss problem",
+  )) ?? false;
+
 export const set_verdicts = async (verdicts: Verdict[]) =>
   (await invokeWithNotify<boolean>(
     "set_verdicts",
@@ -104,6 +157,74 @@ export const get_verdicts = async () =>
     "Could not get verdicts",
   );
 
+export const add_test = async (input: string, answer: string) =>
+  await invokeWithNotify<Verdict>(
+    "add_test",
+    { input, answer },
+    "Test case added",
+    "Could not add test case",
+  );
+
+export const update_test = async (
+  id: string,
+  input: string | null,
+  answer: string | null,
+) =>
+  (await invokeWithNotify<null>(
+    "update_test",
+    { id, input, answer },
+    undefined,
+    "Could not update test case",
+  )) ?? false;
+
+export const delete_test = async (id: string) =>
+  (await invokeWithNotify<null>(
+    "delete_test",
+    { id },
+    undefined,
+    "Could not delete test case",
+  )) ?? false;
+
+export const duplicate_test = async (id: string) =>
+  await invokeWithNotify<Verdict>(
+    "duplicate_test",
+    { id },
+    undefined,
+    "Could not duplicate test case",
+  );
+
+export const reorder_tests = async (ids: string[]) =>
+  (await invokeWithNotify<null>(
+    "reorder_tests",
+    { ids },
+    undefined,
+    "Could not reorder test cases",
+  )) ?? false;
+
+export const set_test_source = async (id: string, source: TestSource) =>
+  (await invokeWithNotify<null>(
+    "set_test_source",
+    { id, source },
+    undefined,
+    "Could not set test case source",
+  )) ?? false;
+
+export const get_effective_config = async () =>
+  await invokeWithNotify<EffectiveConfig>(
+    "get_effective_config",
+    {},
+    undefined,
+    "Could not get the config",
+  );
+
+export const validate_config = async () =>
+  (await invokeWithNotify<Diagnostic[]>(
+    "validate_config",
+    {},
+    undefined,
+    "Could not validate the config",
+  )) ?? [];
+
 export const read_config = async () =>
   (await invokeWithNotify<null>(
     "read_config",
@@ -145,3 +266,85 @@ export const submit = async () =>
 
 export const run = async () =>
   await invokeWithNotify<null>("test", {}, undefined, "Could not run test");
+
+export const set_interactor = async (interactor: Program | null) =>
+  (await invokeWithNotify<boolean>(
+    "set_interactor",
+    { interactor },
+    "Interactor set",
+    "Could not set interactor",
+  )) ?? false;
+
+export const set_checker = async (checker: Checker | null) =>
+  (await invokeWithNotify<boolean>(
+    "set_checker",
+    { checker },
+    "Checker set",
+    "Could not set checker",
+  )) ?? false;
+
+export const set_comparator = async (
+  comparator: Comparator | null,
+  epsilon: number | null,
+) =>
+  (await invokeWithNotify<boolean>(
+    "set_comparator",
+    { comparator, epsilon },
+    "Comparator set",
+    "Could not set comparator",
+  )) ?? false;
+
+export const set_generator = async (generator: Program | null) =>
+  (await invokeWithNotify<boolean>(
+    "set_generator",
+    { generator },
+    "Generator set",
+    "Could not set generator",
+  )) ?? false;
+
+export const set_brute = async (brute: Program | null) =>
+  (await invokeWithNotify<boolean>(
+    "set_brute",
+    { brute },
+    "Brute force solution set",
+    "Could not set brute force solution",
+  )) ?? false;
+
+export const stress = async (iterations: number, seed: number | null = null) =>
+  await invokeWithNotify<Verdict | null>(
+    "stress",
+    { iterations, seed },
+    undefined,
+    "Stress test failed",
+  );
+
+export const minimize = async (index: number) =>
+  await invokeWithNotify<Verdict>(
+    "minimize",
+    { index },
+    "Minimized test added",
+    "Could not minimize test",
+  );
+
+export const stop_stress = async () =>
+  await invokeWithNotify<null>("stop_stress", {}, "Stress test stopped");
+
+export const get_config_schema = async () =>
+  await invokeWithNotify<Record<string, any>>(
+    "get_config_schema",
+    {},
+    undefined,
+    "Could not get config schema",
+  );
+
+export const set_config_value = async (
+  key: string,
+  value: any,
+  scope: ConfigScope = "workspace",
+) =>
+  await invokeWithNotify<Record<string, any>>(
+    "set_config_value",
+    { key, value, scope },
+    undefined,
+    "Could not update config",
+  );